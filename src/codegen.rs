@@ -0,0 +1,476 @@
+//! unoptimized x86-64 assembly, AT&T syntax, for the System V ABI
+//!
+//! Every parameter, local variable and temporary of a method has a
+//! stack slot below `%rbp`, except the parameters after the sixth,
+//! which stay where the caller pushed them. Each instruction of the
+//! three-address code loads its operands into `%rax` and `%r10`,
+//! computes, and stores the result back. Fields are zeroed in `.bss`
+//! and string literals go to `.rodata`. Ints are computed on 32 bits
+//! and kept sign-extended in their 8-byte slots.
+
+use crate::tac::{BinaryOp, CalloutArg, Function, Instr, Label, Operand, Place, Program, UnaryOp, Var};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+const ARG_REGS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+
+const BOUNDS_ERROR: &str = ".Lbounds_error";
+const FALL_OFF_ERROR: &str = ".Lfall_off_error";
+
+/// symbol of a field
+fn field(p: &Program, v: Var) -> String {
+    format!(".Lfield_{}", p.var(v).name)
+}
+
+/// symbol of a method; only `main` keeps its name, so the others cannot
+/// take the place of a libc function called by a callout or the runtime
+fn method(name: &str) -> String {
+    match name {
+        "main" => String::from("main"),
+        _ => format!("__decaf_{}", name),
+    }
+}
+
+/// `s` as the content of a quoted assembler string
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for b in s.bytes() {
+        match b {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            b'\n' => out.push_str("\\n"),
+            b'\t' => out.push_str("\\t"),
+            0x20..=0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    out
+}
+
+/// Code of one method
+struct FunctionGen<'a> {
+    p: &'a Program,
+    f: &'a Function,
+    /// index of the method, making its labels unique
+    index: usize,
+    /// `%rbp` offset of every parameter and local variable
+    slots: HashMap<Var, i64>,
+    /// `%rbp` offset of the first temporary; the others follow below
+    temps: i64,
+    /// number of labels made by `local_label`
+    local_labels: usize,
+    out: String,
+}
+
+impl FunctionGen<'_> {
+    fn line(&mut self, s: &str) {
+        self.out.push_str("    ");
+        self.out.push_str(s);
+        self.out.push('\n');
+    }
+
+    fn label(&self, l: Label) -> String {
+        format!(".L{}_{}", self.index, l.0)
+    }
+
+    /// new label for a branch inside one instruction
+    fn local_label(&mut self) -> String {
+        self.local_labels += 1;
+        format!(".Llocal{}_{}", self.index, self.local_labels)
+    }
+
+    /// memory or immediate operand
+    fn operand(&self, o: Operand) -> String {
+        match o {
+            Operand::Const(n) => format!("${}", n),
+            Operand::Temp(t) => format!("{}(%rbp)", self.temps - 8 * t.0 as i64),
+            Operand::Var(v) => match self.slots.get(&v) {
+                Some(off) => format!("{}(%rbp)", off),
+                None => format!("{}(%rip)", field(self.p, v)),
+            },
+        }
+    }
+
+    fn load(&mut self, o: Operand, reg: &str) {
+        let src = self.operand(o);
+        self.line(&format!("movq {}, {}", src, reg));
+    }
+
+    fn store(&mut self, reg: &str, dst: Place) {
+        let dst = self.operand(dst.into());
+        self.line(&format!("movq {}, {}", reg, dst));
+    }
+
+    /// `%r10` = address of the array `v`
+    fn array(&mut self, v: Var) {
+        let a = field(self.p, v);
+        self.line(&format!("leaq {}(%rip), %r10", a));
+    }
+
+    /// Move a call argument into `reg`
+    fn arg(&mut self, a: CalloutArg, reg: &str) {
+        match a {
+            CalloutArg::Operand(o) => self.load(o, reg),
+            CalloutArg::Str(s) => self.line(&format!("leaq .Lstr{}(%rip), {}", s, reg)),
+        }
+    }
+
+    /// Pass `args` and call `target`, keeping `%rsp` 16-byte aligned
+    fn call(&mut self, target: &str, args: &[CalloutArg], variadic: bool) {
+        let on_stack = args.len().saturating_sub(ARG_REGS.len());
+        let pad = on_stack % 2 == 1;
+        if pad {
+            self.line("subq $8, %rsp");
+        }
+        for &a in args.iter().skip(ARG_REGS.len()).rev() {
+            self.arg(a, "%rax");
+            self.line("pushq %rax");
+        }
+        for (&a, reg) in args.iter().zip(ARG_REGS) {
+            self.arg(a, reg);
+        }
+        if variadic {
+            // no vector registers are used
+            self.line("movl $0, %eax");
+        }
+        self.line(&format!("call {}", target));
+        let pop = 8 * (on_stack + pad as usize);
+        if pop > 0 {
+            self.line(&format!("addq ${}, %rsp", pop));
+        }
+    }
+
+    fn instr(&mut self, i: &Instr) {
+        match i {
+            Instr::Copy { dst, src } => {
+                self.load(*src, "%rax");
+                self.store("%rax", *dst);
+            }
+            Instr::Unary { dst, op, src } => {
+                self.load(*src, "%rax");
+                match op {
+                    UnaryOp::Neg => {
+                        self.line("negl %eax");
+                        self.line("cltq");
+                    }
+                    UnaryOp::Not => self.line("xorq $1, %rax"),
+                }
+                self.store("%rax", *dst);
+            }
+            Instr::Binary { dst, op, lhs, rhs } => {
+                self.load(*lhs, "%rax");
+                self.load(*rhs, "%r10");
+                // ints are 32 bits wide and kept sign-extended in their slots
+                match op {
+                    BinaryOp::Add => self.line("addl %r10d, %eax"),
+                    BinaryOp::Sub => self.line("subl %r10d, %eax"),
+                    BinaryOp::Mul => self.line("imull %r10d, %eax"),
+                    BinaryOp::Div | BinaryOp::Mod => {
+                        // `idivl` traps on INT_MIN / -1, so a divisor of -1
+                        // negates instead, wrapping like the other operators
+                        let (idiv, done) = (self.local_label(), self.local_label());
+                        self.line("cmpl $-1, %r10d");
+                        self.line(&format!("jne {}", idiv));
+                        if *op == BinaryOp::Div {
+                            self.line("negl %eax");
+                        } else {
+                            self.line("movl $0, %eax");
+                        }
+                        self.line(&format!("jmp {}", done));
+                        self.out.push_str(&format!("{}:\n", idiv));
+                        self.line("cltd");
+                        self.line("idivl %r10d");
+                        if *op == BinaryOp::Mod {
+                            self.line("movl %edx, %eax");
+                        }
+                        self.out.push_str(&format!("{}:\n", done));
+                    }
+                    _ => {
+                        let set = match op {
+                            BinaryOp::Eq => "sete",
+                            BinaryOp::Ne => "setne",
+                            BinaryOp::Lt => "setl",
+                            BinaryOp::Le => "setle",
+                            BinaryOp::Gt => "setg",
+                            _ => "setge",
+                        };
+                        self.line("cmpq %r10, %rax");
+                        self.line(&format!("{} %al", set));
+                        self.line("movzbq %al, %rax");
+                    }
+                }
+                if matches!(op, BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod) {
+                    self.line("cltq");
+                }
+                self.store("%rax", *dst);
+            }
+            Instr::BoundsCheck { index, len } => {
+                self.load(*index, "%rax");
+                self.line("cmpq $0, %rax");
+                self.line(&format!("jl {}", BOUNDS_ERROR));
+                self.line(&format!("cmpq ${}, %rax", len));
+                self.line(&format!("jge {}", BOUNDS_ERROR));
+            }
+            Instr::Load { dst, array, index } => {
+                self.load(*index, "%rax");
+                self.array(*array);
+                self.line("movq (%r10,%rax,8), %rax");
+                self.store("%rax", *dst);
+            }
+            Instr::Store { array, index, src } => {
+                self.load(*src, "%r11");
+                self.load(*index, "%rax");
+                self.array(*array);
+                self.line("movq %r11, (%r10,%rax,8)");
+            }
+            Instr::Call { dst, method: method_name, args } => {
+                let args: Vec<CalloutArg> = args.iter().map(|&a| CalloutArg::Operand(a)).collect();
+                self.call(&method(method_name), &args, false);
+                if let Some(d) = dst {
+                    self.store("%rax", *d);
+                }
+            }
+            Instr::Callout { dst, name, args } => {
+                self.call(&format!("{}@PLT", name), args, true);
+                if let Some(d) = dst {
+                    // callouts return a C int
+                    self.line("cltq");
+                    self.store("%rax", *d);
+                }
+            }
+            Instr::Label(l) => {
+                let l = self.label(*l);
+                self.out.push_str(&format!("{}:\n", l));
+            }
+            Instr::Jump(l) => {
+                let l = self.label(*l);
+                self.line(&format!("jmp {}", l));
+            }
+            Instr::Branch { cond, if_true, if_false } => {
+                self.load(*cond, "%rax");
+                self.line("testq %rax, %rax");
+                let (t, f) = (self.label(*if_true), self.label(*if_false));
+                self.line(&format!("jne {}", t));
+                self.line(&format!("jmp {}", f));
+            }
+            Instr::Return(v) => {
+                match v {
+                    Some(v) => self.load(*v, "%rax"),
+                    // `main` exits with status 0
+                    None => self.line("movl $0, %eax"),
+                }
+                self.line("leave");
+                self.line("ret");
+            }
+            Instr::FallOff => self.line(&format!("jmp {}", FALL_OFF_ERROR)),
+        }
+    }
+
+    fn generate(mut self) -> String {
+        let mut size = 0;
+        for (i, &v) in self.f.params.iter().enumerate() {
+            if i < ARG_REGS.len() {
+                size += 8;
+                self.slots.insert(v, -size);
+            } else {
+                // pushed by the caller, above the return address
+                self.slots.insert(v, 16 + 8 * (i - ARG_REGS.len()) as i64);
+            }
+        }
+        for &v in &self.f.locals {
+            size += 8;
+            self.slots.insert(v, -size);
+        }
+        self.temps = -size - 8;
+        size += 8 * self.f.temps as i64;
+        // keep %rsp 16-byte aligned, as it is after `pushq %rbp`
+        size = (size + 15) / 16 * 16;
+
+        self.out.push_str(&format!("{}:\n", method(&self.f.name)));
+        self.line("pushq %rbp");
+        self.line("movq %rsp, %rbp");
+        if size > 0 {
+            self.line(&format!("subq ${}, %rsp", size));
+        }
+        for (i, &v) in self.f.params.iter().take(ARG_REGS.len()).enumerate() {
+            self.store(ARG_REGS[i], Place::Var(v));
+        }
+        for i in &self.f.code {
+            self.instr(i);
+        }
+        self.out
+    }
+}
+
+/// Assembly of `p`, with `main` as the entry point called by libc
+pub fn generate(p: &Program) -> String {
+    let mut out = String::new();
+    if !p.globals.is_empty() {
+        out.push_str("    .bss\n    .align 8\n");
+        for &g in &p.globals {
+            let size = 8 * p.var(g).len.unwrap_or(1);
+            let _ = writeln!(out, "{}:\n    .zero {}", field(p, g), size);
+        }
+    }
+
+    out.push_str("    .section .rodata\n");
+    for (i, s) in p.strings.iter().enumerate() {
+        let _ = writeln!(out, ".Lstr{}:\n    .string \"{}\"", i, escape(s));
+    }
+    out.push_str(".Lbounds_msg:\n    .string \"runtime error: array index out of bounds\\n\"\n");
+    out.push_str(".Lfall_off_msg:\n    .string \"runtime error: control fell off the end of a method returning a value\\n\"\n");
+
+    out.push_str("    .text\n    .globl main\n");
+    for (index, f) in p.functions.iter().enumerate() {
+        let g = FunctionGen {
+            p,
+            f,
+            index,
+            slots: HashMap::new(),
+            temps: 0,
+            local_labels: 0,
+            out: String::new(),
+        };
+        out.push_str(&g.generate());
+    }
+
+    // runtime errors print a message to stderr and exit with status -1
+    for (label, msg) in [(BOUNDS_ERROR, ".Lbounds_msg"), (FALL_OFF_ERROR, ".Lfall_off_msg")] {
+        let _ = writeln!(out, "{}:\n    leaq {}(%rip), %rdi\n    jmp .Lruntime_error", label, msg);
+    }
+    out.push_str(
+        ".Lruntime_error:
+    andq $-16, %rsp
+    movq stderr@GOTPCREL(%rip), %rax
+    movq (%rax), %rsi
+    call fputs@PLT
+    movl $-1, %edi
+    call exit@PLT
+    .section .note.GNU-stack,\"\",@progbits
+",
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::lower;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    fn assembly(src: &str) -> String {
+        generate(&lower(src))
+    }
+
+    /// exit status and output of `src` built with gcc, which these tests
+    /// need installed
+    fn run(name: &str, src: &str) -> (i32, String) {
+        let dir: PathBuf = std::env::temp_dir().join(format!("decafc-codegen-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (asm, exe) = (dir.join("a.s"), dir.join("a.out"));
+        std::fs::write(&asm, assembly(src)).unwrap();
+        let built = Command::new("gcc")
+            .arg(&asm)
+            .arg("-o")
+            .arg(&exe)
+            .status()
+            .expect("gcc is needed to run the generated code");
+        assert!(built.success(), "gcc failed");
+        let out = Command::new(&exe).output().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        (out.status.code().unwrap(), String::from_utf8(out.stdout).unwrap())
+    }
+
+    #[test]
+    fn test_layout() {
+        let asm = assembly(
+            "class Program { int a[3], n; void main() { callout(\"printf\", \"%d\\n\", a[n]); \
+             callout(\"printf\", \"\\\"%d\\\"\\n\", n); } }",
+        );
+        assert!(asm.contains("    .bss\n    .align 8\n.Lfield_a:\n    .zero 24\n.Lfield_n:\n    .zero 8\n"));
+        assert!(asm.contains(".Lstr0:\n    .string \"%d\\n\"\n.Lstr1:\n    .string \"\\\"%d\\\"\\n\"\n"));
+        assert!(asm.contains("    .globl main\nmain:\n    pushq %rbp\n    movq %rsp, %rbp\n"));
+        assert!(asm.contains("    movl $0, %eax\n    call printf@PLT\n"));
+    }
+
+    #[test]
+    fn test_stack_args() {
+        let asm = assembly(
+            "class Program { int f(int a, int b, int c, int d, int e, int f, int g) { return g; } \
+             void main() { f(1, 2, 3, 4, 5, 6, 7); } }",
+        );
+        // one argument on the stack, padded to keep the alignment
+        assert!(asm.contains("    subq $8, %rsp\n    movq $7, %rax\n    pushq %rax\n"));
+        assert!(asm.contains("    call __decaf_f\n    addq $16, %rsp\n"));
+        assert!(asm.contains("    movq 16(%rbp), %rax\n"));
+    }
+
+    #[test]
+    fn test_run() {
+        let src = "class Program { int a[10]; \
+                   int sum(int a1, int a2, int a3, int a4, int a5, int a6, int a7, int a8) { \
+                   return a1 + a2 + a3 + a4 + a5 + a6 + a7 * 10 + a8 * 100; } \
+                   int fact(int n) { if (n <= 1) { return 1; } return n * fact(n - 1); } \
+                   void main() { int x; boolean b; \
+                   for i = 0, 10 { if (i == 7) { break; } if (i % 2 == 1) { continue; } a[i] += i * 3; } \
+                   b = a[4] == 12 && !(a[3] > 0); \
+                   x = sum(1, 2, 3, 4, 5, 6, 7, 8) - fact(5) / 2; \
+                   callout(\"printf\", \"%d %d %d %d\\n\", a[6], b, x, -x % 7); } }";
+        let (status, out) = run("run", src);
+        assert_eq!(status, 0);
+        assert_eq!(out, "18 1 831 -5\n");
+    }
+
+    #[test]
+    fn test_compound_assign_order() {
+        // the destination is read before the value in both forms
+        let src = "class Program { int g; int set() { g = 5; return 1; } \
+                   void main() { int x; g = 1; g += set(); x = g; g = 1; g = g + set(); \
+                   callout(\"printf\", \"%d %d\\n\", x, g); } }";
+        let (status, out) = run("compound", src);
+        assert_eq!(status, 0);
+        assert_eq!(out, "2 2\n");
+    }
+
+    #[test]
+    fn test_libc_names() {
+        // user methods named like libc functions do not replace them
+        let src = "class Program { int a[1]; void printf(int x) { } void exit(int x) { } \
+                   void main() { int i; printf(1); exit(2); callout(\"printf\", \"%d\\n\", 3); \
+                   i = 1; a[i] = 0; } }";
+        let (status, out) = run("libc-names", src);
+        assert_eq!(status, 255);
+        assert_eq!(out, "3\n");
+    }
+
+    #[test]
+    fn test_overflow() {
+        let src = "class Program { void main() { int x, y; x = 2147483647; x = x + 1; y = -x; \
+                   if (x < 0) { callout(\"printf\", \"wrap %d %d %d\\n\", x, y, x * 2); } \
+                   else { callout(\"printf\", \"no wrap %d\\n\", x); } } }";
+        let (status, out) = run("overflow", src);
+        assert_eq!(status, 0);
+        assert_eq!(out, "wrap -2147483648 -2147483648 0\n");
+    }
+
+    #[test]
+    fn test_div_by_minus_one() {
+        // INT_MIN / -1 wraps instead of trapping
+        let src = "class Program { void main() { int x, y; x = -2147483648; y = -1; \
+                   callout(\"printf\", \"%d %d %d %d\\n\", x / y, x % y, 7 / y, -7 % y); } }";
+        let (status, out) = run("div-minus-one", src);
+        assert_eq!(status, 0);
+        assert_eq!(out, "-2147483648 0 -7 0\n");
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let bounds = "class Program { int a[2]; void main() { int i; i = 2; a[i] = 1; } }";
+        let fall_off = "class Program { int f() { if (false) { return 1; } } void main() { f(); } }";
+        for (name, src) in [("bounds", bounds), ("fall-off", fall_off)] {
+            let (status, _) = run(name, src);
+            assert_eq!(status, 255);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! machine-readable rendering of diagnostics, one JSON object per line
+
+use super::{Diagnostic, Label, SourceFile};
+use std::fmt::Write;
+
+/// JSON string literal of `s`
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn span_json(file: &SourceFile, label: &Label) -> String {
+    let (line_start, column_start) = file.line_col(label.span.start);
+    let (line_end, column_end) = file.line_col(label.span.end);
+    format!(
+        "{{\"file\":{},\"byte_start\":{},\"byte_end\":{},\"line_start\":{},\"column_start\":{},\"line_end\":{},\"column_end\":{},\"label\":{}}}",
+        quote(file.name),
+        label.span.start,
+        label.span.end,
+        line_start,
+        column_start,
+        line_end,
+        column_end,
+        quote(&label.message)
+    )
+}
+
+/// Serialize a diagnostic as a single-line JSON object
+pub fn to_json(file: &SourceFile, d: &Diagnostic) -> String {
+    let code = match d.code {
+        Some(c) => quote(c),
+        None => String::from("null"),
+    };
+    let primary = match &d.primary {
+        Some(l) => span_json(file, l),
+        None => String::from("null"),
+    };
+    let secondary: Vec<String> = d.secondary.iter().map(|l| span_json(file, l)).collect();
+    let notes: Vec<String> = d.notes.iter().map(|n| quote(n)).collect();
+    format!(
+        "{{\"code\":{},\"severity\":{},\"message\":{},\"file\":{},\"primary\":{},\"secondary\":[{}],\"notes\":[{}]}}",
+        code,
+        quote(&d.severity.to_string()),
+        quote(&d.message),
+        quote(file.name),
+        primary,
+        secondary.join(","),
+        notes.join(",")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span::Span;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("a\"b\\c\n\u{1}"), r#""a\"b\\c\n\u0001""#);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let src = "class Program {\n  int a;\n  int a;\n}\n";
+        let file = SourceFile::new("a.dcf", src);
+        let d = Diagnostic::error("`a` is declared twice")
+            .with_code("E0001")
+            .with_primary(Span::new(31, 32), "redeclared")
+            .with_secondary(Span::new(22, 23), "first declared")
+            .with_note("decaf rule: \"no\" duplicates");
+        let expected = concat!(
+            r#"{"code":"E0001","severity":"error","message":"`a` is declared twice","file":"a.dcf","#,
+            r#""primary":{"file":"a.dcf","byte_start":31,"byte_end":32,"line_start":3,"column_start":7,"line_end":3,"column_end":8,"label":"redeclared"},"#,
+            r#""secondary":[{"file":"a.dcf","byte_start":22,"byte_end":23,"line_start":2,"column_start":7,"line_end":2,"column_end":8,"label":"first declared"}],"#,
+            r#""notes":["decaf rule: \"no\" duplicates"]}"#
+        );
+        assert_eq!(to_json(&file, &d), expected);
+
+        let d = Diagnostic::warning("no location");
+        assert_eq!(
+            to_json(&file, &d),
+            r#"{"code":null,"severity":"warning","message":"no location","file":"a.dcf","primary":null,"secondary":[],"notes":[]}"#
+        );
+    }
+}
@@ -0,0 +1,194 @@
+//! compiler diagnostics and their rendering
+
+mod json;
+mod render;
+pub use json::*;
+pub use render::*;
+
+use crate::error::{CompileError, ParseError};
+use crate::scanner::LexicalError;
+use crate::semantic_analyzer::lints::Warning;
+use crate::semantic_analyzer::SemanticCheckError;
+use crate::span::Span;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// Source range annotated with a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    pub message: String,
+    /// where the error is
+    pub primary: Option<Label>,
+    /// related locations, e.g. a previous declaration
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            code: None,
+            message: message.into(),
+            primary: None,
+            secondary: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_primary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.primary = Some(Label::new(span, message));
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.secondary.push(Label::new(span, message));
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+}
+
+impl From<&SemanticCheckError> for Diagnostic {
+    fn from(e: &SemanticCheckError) -> Self {
+        let d = Diagnostic::error(e.to_string())
+            .with_code(e.code())
+            .with_primary(e.span(), "")
+            .with_note(format!("decaf rule: {}", e.rule()));
+        let d = match e.help() {
+            Some(help) => d.with_note(help),
+            None => d,
+        };
+        match e.related() {
+            Some((span, message)) => d.with_secondary(span, message),
+            None => d,
+        }
+    }
+}
+
+/// Diagnostic for a lint, reported as a warning or as an error if denied
+fn lint_diagnostic(w: &Warning, severity: Severity) -> Diagnostic {
+    let name = w.lint.name();
+    let note = match severity {
+        Severity::Warning => format!("`{}` is on by default; `-A {}` allows it", name, name),
+        Severity::Error => format!("`{}` is denied by `-D {}`", name, name),
+    };
+    let d = Diagnostic::new(severity, w.to_string())
+        .with_code(name)
+        .with_primary(w.span, "")
+        .with_note(note);
+    match &w.related {
+        Some((span, message)) => d.with_secondary(*span, message.clone()),
+        None => d,
+    }
+}
+
+impl From<&Warning> for Diagnostic {
+    fn from(w: &Warning) -> Self {
+        lint_diagnostic(w, Severity::Warning)
+    }
+}
+
+fn expected_note(expected: &[String]) -> Option<String> {
+    match expected {
+        [] => None,
+        [e] => Some(format!("expected {}", e)),
+        _ => Some(format!("expected one of {}", expected.join(", "))),
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(e: &ParseError) -> Self {
+        match e {
+            ParseError::InvalidToken { location } => Diagnostic::error("invalid token")
+                .with_primary(Span::new(*location, *location + 1), "unrecognized character"),
+            ParseError::UnrecognizedEOF { location, expected } => {
+                let d = Diagnostic::error("unexpected end of file")
+                    .with_primary(Span::new(*location, *location), "");
+                match expected_note(expected) {
+                    Some(n) => d.with_note(n),
+                    None => d,
+                }
+            }
+            ParseError::UnrecognizedToken {
+                token: (l, t, r),
+                expected,
+            } => {
+                let d = Diagnostic::error(format!("unexpected token `{}`", t));
+                match expected_note(expected) {
+                    Some(n) => d.with_primary(Span::new(*l, *r), n),
+                    None => d.with_primary(Span::new(*l, *r), ""),
+                }
+            }
+            ParseError::ExtraToken { token: (l, t, r) } => {
+                Diagnostic::error(format!("extra token `{}`", t)).with_primary(Span::new(*l, *r), "")
+            }
+            ParseError::User { error } => error.into(),
+        }
+    }
+}
+
+impl From<&LexicalError> for Diagnostic {
+    fn from(e: &LexicalError) -> Self {
+        Diagnostic::error(e.to_string()).with_primary(e.span, "")
+    }
+}
+
+impl From<&CompileError> for Diagnostic {
+    fn from(e: &CompileError) -> Self {
+        match e {
+            CompileError::Lexical(e) => e.into(),
+            CompileError::Parse(e) => e.into(),
+            CompileError::Semantic(e) => e.into(),
+            CompileError::Lint(w) => lint_diagnostic(w, Severity::Error),
+            CompileError::Codegen(e) => Diagnostic::error(e.clone()),
+        }
+    }
+}
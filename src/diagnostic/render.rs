@@ -0,0 +1,259 @@
+//! rustc-style rendering of diagnostics
+
+use super::{Diagnostic, Label, Severity};
+use std::fmt::Write;
+
+const TAB_WIDTH: usize = 4;
+
+/// Source code with its file name and line offsets
+pub struct SourceFile<'a> {
+    pub name: &'a str,
+    pub src: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> SourceFile<'a> {
+    pub fn new(name: &'a str, src: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            name,
+            src,
+            line_starts,
+        }
+    }
+
+    /// 1-based line and column (in characters) of a byte offset
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.src.len());
+        let line = self.line_starts.partition_point(|&s| s <= offset) - 1;
+        let col = self.src[self.line_starts[line]..offset].chars().count();
+        (line + 1, col + 1)
+    }
+
+    /// byte offset where a 1-based line starts
+    pub fn line_start(&self, line: usize) -> usize {
+        self.line_starts[line - 1]
+    }
+
+    /// text of a 1-based line without the line break
+    pub fn line(&self, line: usize) -> &'a str {
+        let start = self.line_start(line);
+        let end = match self.line_starts.get(line) {
+            Some(&e) => e,
+            None => self.src.len(),
+        };
+        self.src[start..end].trim_end_matches(['\n', '\r'])
+    }
+}
+
+/// Renders diagnostics as text, optionally with ANSI colours
+pub struct Renderer {
+    color: bool,
+}
+
+#[derive(Clone, Copy)]
+struct Style {
+    start: &'static str,
+}
+
+const BOLD: Style = Style { start: "\x1b[1m" };
+const RED: Style = Style { start: "\x1b[1;31m" };
+const YELLOW: Style = Style { start: "\x1b[1;33m" };
+const BLUE: Style = Style { start: "\x1b[1;34m" };
+const RESET: &str = "\x1b[0m";
+
+/// display width of `s` with tabs expanded
+fn width(s: &str) -> usize {
+    s.chars().map(|c| if c == '\t' { TAB_WIDTH } else { 1 }).sum()
+}
+
+impl Renderer {
+    pub fn new(color: bool) -> Self {
+        Self { color }
+    }
+
+    fn paint(&self, style: &Style, s: &str) -> String {
+        if self.color {
+            format!("{}{}{}", style.start, s, RESET)
+        } else {
+            s.to_string()
+        }
+    }
+
+    fn severity_style(severity: Severity) -> Style {
+        match severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        }
+    }
+
+    pub fn render(&self, file: &SourceFile, d: &Diagnostic) -> String {
+        let mut out = String::new();
+        let style = Self::severity_style(d.severity);
+        let header = match d.code {
+            Some(code) => format!("{}[{}]", d.severity, code),
+            None => d.severity.to_string(),
+        };
+        let _ = writeln!(
+            out,
+            "{}{}",
+            self.paint(&style, &header),
+            self.paint(&BOLD, &format!(": {}", d.message))
+        );
+
+        let labels: Vec<(&Label, bool)> = d
+            .primary
+            .iter()
+            .map(|l| (l, true))
+            .chain(d.secondary.iter().map(|l| (l, false)))
+            .collect();
+        let gutter = labels
+            .iter()
+            .map(|(l, _)| file.line_col(l.span.start).0.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let pad = " ".repeat(gutter);
+        let bar = self.paint(&BLUE, "|");
+
+        match labels.first() {
+            Some((l, _)) => {
+                let (line, col) = file.line_col(l.span.start);
+                let arrow = if d.primary.is_some() { "-->" } else { ":::" };
+                let _ = writeln!(out, "{}{} {}:{}:{}", pad, self.paint(&BLUE, arrow), file.name, line, col);
+            }
+            None => {
+                let _ = writeln!(out, "{}{} {}", pad, self.paint(&BLUE, "-->"), file.name);
+            }
+        }
+
+        for (i, &(label, is_primary)) in labels.iter().enumerate() {
+            let (line, col) = file.line_col(label.span.start);
+            if i > 0 {
+                let _ = writeln!(out, "{}{} {}:{}:{}", pad, self.paint(&BLUE, ":::"), file.name, line, col);
+            }
+            let text = file.line(line);
+            let line_start = file.line_start(line);
+            // multi-line spans are underlined up to the end of their first line
+            // and a span starting past the line text, such as at its newline, keeps one marker
+            let line_end = line_start + text.len();
+            let start = label.span.start.min(line_end);
+            let end = label.span.end.clamp(start, line_end);
+            let before = &text[..start - line_start];
+            let underlined = &file.src[start..end];
+            let (marker, marker_style) = if is_primary { ("^", style) } else { ("-", BLUE) };
+            let markers = marker.repeat(width(underlined).max(1));
+
+            let _ = writeln!(out, "{} {}", pad, bar);
+            let _ = writeln!(
+                out,
+                "{} {} {}",
+                self.paint(&BLUE, &format!("{:>w$}", line, w = gutter)),
+                bar,
+                text.replace('\t', &" ".repeat(TAB_WIDTH))
+            );
+            let underline = if label.message.is_empty() {
+                markers
+            } else {
+                format!("{} {}", markers, label.message)
+            };
+            let _ = writeln!(
+                out,
+                "{} {} {}{}",
+                pad,
+                bar,
+                " ".repeat(width(before)),
+                self.paint(&marker_style, &underline)
+            );
+        }
+
+        if !d.notes.is_empty() {
+            let _ = writeln!(out, "{} {}", pad, bar);
+        }
+        for note in &d.notes {
+            let _ = writeln!(out, "{} {} {}: {}", pad, self.paint(&BLUE, "="), self.paint(&BOLD, "note"), note);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span::Span;
+
+    #[test]
+    fn test_line_col() {
+        let file = SourceFile::new("a.dcf", "ab\n\tcd\n\nx");
+        assert_eq!(file.line_col(0), (1, 1));
+        assert_eq!(file.line_col(2), (1, 3));
+        assert_eq!(file.line_col(4), (2, 2));
+        assert_eq!(file.line_col(7), (3, 1));
+        assert_eq!(file.line_col(8), (4, 1));
+        assert_eq!(file.line(2), "\tcd");
+        assert_eq!(file.line(3), "");
+    }
+
+    #[test]
+    fn test_render() {
+        let src = "class Program {\n  void main() {\n\tx = 1;\n  }\n}\n";
+        let file = SourceFile::new("a.dcf", src);
+        let start = src.find('x').unwrap();
+        let d = Diagnostic::error("cannot find `x` in this scope")
+            .with_code("E0002")
+            .with_primary(Span::new(start, start + 1), "not found")
+            .with_note("decaf rule: no identifier is used before it is declared");
+        let expected = "\
+error[E0002]: cannot find `x` in this scope
+ --> a.dcf:3:2
+  |
+3 |     x = 1;
+  |     ^ not found
+  |
+  = note: decaf rule: no identifier is used before it is declared
+";
+        assert_eq!(Renderer::new(false).render(&file, &d), expected);
+    }
+
+    #[test]
+    fn test_render_secondary() {
+        let src = "class Program {\n  int a;\n  int a;\n}\n";
+        let file = SourceFile::new("a.dcf", src);
+        let first = src.find("a;").unwrap();
+        let second = src.rfind("a;").unwrap();
+        let d = Diagnostic::error("`a` is declared twice in the same scope")
+            .with_primary(Span::new(second, second + 1), "redeclared here")
+            .with_secondary(Span::new(first, first + 1), "first declared here");
+        let expected = "\
+error: `a` is declared twice in the same scope
+ --> a.dcf:3:7
+  |
+3 |   int a;
+  |       ^ redeclared here
+ ::: a.dcf:2:7
+  |
+2 |   int a;
+  |       - first declared here
+";
+        assert_eq!(Renderer::new(false).render(&file, &d), expected);
+    }
+
+    #[test]
+    fn test_render_span_past_line_end() {
+        // the span starts on the `\n` of a `\r\n` line ending
+        let src = "class Program {\r\n}\r\n";
+        let file = SourceFile::new("a.dcf", src);
+        let start = src.find('\n').unwrap();
+        let d = Diagnostic::error("unexpected end of line")
+            .with_primary(Span::new(start, start + 1), "here");
+        let expected = "\
+error: unexpected end of line
+ --> a.dcf:1:17
+  |
+1 | class Program {
+  |                ^ here
+";
+        assert_eq!(Renderer::new(false).render(&file, &d), expected);
+    }
+}
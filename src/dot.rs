@@ -0,0 +1,613 @@
+//! Graphviz export of the token tree, the IR and control-flow graphs
+
+use crate::formatter;
+use crate::semantic_analyzer::ir;
+use crate::semantic_analyzer::printer;
+use crate::tac;
+use crate::token;
+use std::collections::HashMap;
+
+const INDENT: &str = "    ";
+
+/// `s` as the content of a quoted dot string
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Text of a dot digraph, built node by node
+struct Graph {
+    out: String,
+    nodes: usize,
+    depth: usize,
+}
+
+impl Graph {
+    fn new(name: &str) -> Self {
+        let mut g = Self {
+            out: String::new(),
+            nodes: 0,
+            depth: 0,
+        };
+        g.open(&format!("digraph \"{}\" {{", escape(name)));
+        g.line("node [shape=box, fontname=monospace];");
+        g
+    }
+
+    fn line(&mut self, s: &str) {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(s);
+        self.out.push('\n');
+    }
+
+    fn open(&mut self, s: &str) {
+        self.line(s);
+        self.depth += 1;
+    }
+
+    fn close(&mut self) {
+        self.depth -= 1;
+        self.line("}");
+    }
+
+    fn subgraph(&mut self, name: &str) {
+        let n = self.nodes;
+        self.open(&format!("subgraph cluster_{} {{", n));
+        self.line(&format!("label=\"{}\";", escape(name)));
+    }
+
+    /// new node, with extra attributes `attrs` if not empty
+    fn node_with(&mut self, label: &str, attrs: &str) -> usize {
+        let n = self.nodes;
+        self.nodes += 1;
+        let attrs = if attrs.is_empty() { String::new() } else { format!(", {}", attrs) };
+        self.line(&format!("n{} [label=\"{}\"{}];", n, escape(label), attrs));
+        n
+    }
+
+    fn node(&mut self, label: &str) -> usize {
+        self.node_with(label, "")
+    }
+
+    fn edge_with(&mut self, from: usize, to: usize, attrs: &str) {
+        if attrs.is_empty() {
+            self.line(&format!("n{} -> n{};", from, to));
+        } else {
+            self.line(&format!("n{} -> n{} [{}];", from, to, attrs));
+        }
+    }
+
+    /// edge labelled `label` if not empty
+    fn edge(&mut self, from: usize, to: usize, label: &str) {
+        if label.is_empty() {
+            self.edge_with(from, to, "");
+        } else {
+            self.edge_with(from, to, &format!("label=\"{}\"", escape(label)));
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.close();
+        self.out
+    }
+}
+
+/// Token tree of `p`
+pub fn ast(p: &token::Program) -> String {
+    let mut g = Graph::new("ast");
+    let root = g.node("Program");
+    for f in &p.field_decls {
+        let names: Vec<String> = f
+            .loc
+            .iter()
+            .map(|d| match &d.arr_size {
+                Some(n) => format!("{}[{}]", d.name.name, n),
+                None => d.name.name.clone(),
+            })
+            .collect();
+        let n = g.node(&format!("FieldDecl {}\n{}", formatter::type_name(&f.type_), names.join(", ")));
+        g.edge(root, n, "");
+    }
+    for m in &p.method_decls {
+        let n = g.node(&format!("MethodDecl {}\n{}", m.name.name, formatter::type_name(&m.return_type)));
+        g.edge(root, n, "");
+        for a in &m.args {
+            let arg = g.node(&format!("MethodArg {}\n{}", a.name.name, formatter::type_name(&a.type_)));
+            g.edge(n, arg, "");
+        }
+        let b = ast_block(&mut g, &m.block);
+        g.edge(n, b, "");
+    }
+    g.finish()
+}
+
+fn ast_block(g: &mut Graph, b: &token::Block) -> usize {
+    let n = g.node("Block");
+    for d in &b.var_decls {
+        let names: Vec<&str> = d.identifiers.iter().map(|i| i.name.as_str()).collect();
+        let d = g.node(&format!("VarDecl {}\n{}", formatter::type_name(&d.type_), names.join(", ")));
+        g.edge(n, d, "");
+    }
+    for s in &b.statements {
+        let s = ast_statement(g, s);
+        g.edge(n, s, "");
+    }
+    n
+}
+
+fn ast_statement(g: &mut Graph, s: &token::Statement) -> usize {
+    match s {
+        token::Statement::Assign(a) => {
+            let n = g.node(&format!("Assign {}", formatter::assign_op(&a.op)));
+            let dst = ast_location(g, &a.dst);
+            g.edge(n, dst, "dst");
+            let val = ast_expr(g, &a.val);
+            g.edge(n, val, "val");
+            n
+        }
+        token::Statement::MethodCall(c) => ast_call(g, c),
+        token::Statement::IfElse(i) => {
+            let n = g.node("IfElse");
+            let cond = ast_expr(g, &i.cond);
+            g.edge(n, cond, "cond");
+            let t = ast_block(g, &i.true_block);
+            g.edge(n, t, "then");
+            if let Some(b) = &i.false_block {
+                let f = ast_block(g, b);
+                g.edge(n, f, "else");
+            }
+            n
+        }
+        token::Statement::Loop(l) => {
+            let n = g.node(&format!("For {}", l.index_var.name));
+            let start = ast_expr(g, &l.start);
+            g.edge(n, start, "start");
+            let end = ast_expr(g, &l.end);
+            g.edge(n, end, "end");
+            let b = ast_block(g, &l.block);
+            g.edge(n, b, "body");
+            n
+        }
+        token::Statement::Return(r) => {
+            let n = g.node("Return");
+            if let Some(v) = &r.val {
+                let v = ast_expr(g, v);
+                g.edge(n, v, "");
+            }
+            n
+        }
+        token::Statement::Break(_) => g.node("Break"),
+        token::Statement::Continue(_) => g.node("Continue"),
+        token::Statement::Block(b) => ast_block(g, b),
+    }
+}
+
+fn ast_location(g: &mut Graph, l: &token::Location) -> usize {
+    let n = g.node(&format!("Location {}", l.name.name));
+    if let Some(i) = &l.arr_size {
+        let i = ast_expr(g, i);
+        g.edge(n, i, "index");
+    }
+    n
+}
+
+fn ast_call(g: &mut Graph, c: &token::MethodCall) -> usize {
+    match c {
+        token::MethodCall::Method(m) => {
+            let n = g.node(&format!("Method {}", m.name.name));
+            for a in &m.args {
+                let a = ast_expr(g, a);
+                g.edge(n, a, "");
+            }
+            n
+        }
+        token::MethodCall::Callout(c) => {
+            let n = g.node(&format!("Callout {:?}", c.name));
+            for a in &c.args {
+                let a = match a {
+                    token::CalloutArg::Expr(e) => ast_expr(g, e),
+                    token::CalloutArg::StringLiteral(s, _) => g.node(&format!("StringLiteral {:?}", s)),
+                };
+                g.edge(n, a, "");
+            }
+            n
+        }
+    }
+}
+
+fn ast_expr(g: &mut Graph, e: &token::Expr0) -> usize {
+    match e {
+        token::Expr0::Location(l) => ast_location(g, l),
+        token::Expr0::MethodCall(c) => ast_call(g, c),
+        token::Expr0::Literal(l) => {
+            let text = match &l.kind {
+                token::LiteralKind::Int(s) => s.clone(),
+                token::LiteralKind::Bool(token::BoolLiteral::True) => String::from("true"),
+                token::LiteralKind::Bool(token::BoolLiteral::False) => String::from("false"),
+                token::LiteralKind::Char(c) => format!("{:?}", c),
+            };
+            g.node(&format!("Literal {}", text))
+        }
+        token::Expr0::Unary(u) => {
+            let op = match u.op {
+                token::UnaryOp::NegInt => "-",
+                token::UnaryOp::NegBool => "!",
+            };
+            let n = g.node(&format!("Unary {}", op));
+            let e = ast_expr(g, &u.expr);
+            g.edge(n, e, "");
+            n
+        }
+        token::Expr0::Binary(b) => {
+            let n = g.node(&format!("Binary {}", formatter::binary_op(&b.op)));
+            let lhs = ast_expr(g, &b.lhs);
+            g.edge(n, lhs, "");
+            let rhs = ast_expr(g, &b.rhs);
+            g.edge(n, rhs, "");
+            n
+        }
+    }
+}
+
+/// Label of an IR statement node: its kind and the type of its value
+fn statement_label(s: &ir::Statement, root: &ir::IRRoot) -> String {
+    match s {
+        ir::Statement::Assign(a) => format!(
+            "Assign {} {}\n{}",
+            root.decl(a.dst.decl).name,
+            printer::assign_op(a.op),
+            root.expr(a.val).type_
+        ),
+        ir::Statement::Call(ir::Call::Method(m)) => format!("Call {}", root.method(m.decl).name),
+        ir::Statement::Call(ir::Call::Callout(c)) => format!("Callout {:?}", c.name),
+        ir::Statement::IfElse(_) => String::from("IfElse"),
+        ir::Statement::For(l) => format!("For {}", root.decl(l.index_decl).name),
+        ir::Statement::Return(r) => match r.val {
+            Some(v) => format!("Return\n{}", root.expr(v).type_),
+            None => String::from("Return"),
+        },
+        ir::Statement::Break(_) => String::from("Break"),
+        ir::Statement::Continue(_) => String::from("Continue"),
+        ir::Statement::Block(_) => String::from("Block"),
+    }
+}
+
+/// IR tree, with dashed edges from uses to their declarations
+struct IrGraph<'a> {
+    g: Graph,
+    ir: &'a ir::IRRoot,
+    /// node of each declaration
+    vars: HashMap<ir::DeclId, usize>,
+    methods: HashMap<ir::MethodId, usize>,
+    loops: HashMap<ir::StmtId, usize>,
+}
+
+impl IrGraph<'_> {
+    fn decl(&mut self, label: &str) -> usize {
+        self.g.node_with(label, "style=rounded")
+    }
+
+    fn var_decl(&mut self, id: ir::DeclId, kind: &str) -> usize {
+        let d = self.ir.decl(id);
+        let label = match d.arr_size {
+            Some(size) => format!("{} {}[{}]\n{}", kind, d.name, size, d.type_),
+            None => format!("{} {}\n{}", kind, d.name, d.type_),
+        };
+        let n = self.decl(&label);
+        self.vars.insert(id, n);
+        n
+    }
+
+    /// dashed edge from `n` to the declaration node `d`
+    fn refer(&mut self, n: usize, d: Option<usize>) {
+        if let Some(d) = d {
+            self.g.edge_with(n, d, "style=dashed, constraint=false");
+        }
+    }
+
+    fn method_decl(&mut self, id: ir::MethodId) -> usize {
+        let m = self.ir.method(id);
+        let n = self.decl(&format!("MethodDecl {}\n{}", m.name, m.return_type));
+        self.methods.insert(id, n);
+        for &a in &m.args {
+            let a = self.var_decl(a, "Arg");
+            self.g.edge(n, a, "");
+        }
+        let b = self.block(&m.block);
+        self.g.edge(n, b, "");
+        n
+    }
+
+    fn block(&mut self, b: &ir::Block) -> usize {
+        let n = self.g.node("Block");
+        for &d in &b.var_decls {
+            let d = self.var_decl(d, "VarDecl");
+            self.g.edge(n, d, "");
+        }
+        for &s in &b.statements {
+            let s = self.statement(s);
+            self.g.edge(n, s, "");
+        }
+        n
+    }
+
+    fn statement(&mut self, id: ir::StmtId) -> usize {
+        let s = self.ir.stmt(id);
+        if let ir::Statement::Block(b) = s {
+            return self.block(b);
+        }
+        let label = statement_label(s, self.ir);
+        let n = match s {
+            ir::Statement::For(_) => {
+                let n = self.decl(&label);
+                self.loops.insert(id, n);
+                n
+            }
+            _ => self.g.node(&label),
+        };
+        match s {
+            ir::Statement::Assign(a) => {
+                let dst = self.location(&a.dst);
+                self.g.edge(n, dst, "dst");
+                let val = self.expr(a.val);
+                self.g.edge(n, val, "val");
+            }
+            ir::Statement::Call(c) => self.call_args(n, c),
+            ir::Statement::IfElse(i) => {
+                let cond = self.expr(i.cond);
+                self.g.edge(n, cond, "cond");
+                let b = self.block(&i.true_block);
+                self.g.edge(n, b, "then");
+                if let Some(b) = &i.false_block {
+                    let b = self.block(b);
+                    self.g.edge(n, b, "else");
+                }
+            }
+            ir::Statement::For(l) => {
+                let index = self.var_decl(l.index_decl, "Index");
+                self.g.edge(n, index, "index");
+                let start = self.expr(l.start);
+                self.g.edge(n, start, "start");
+                let end = self.expr(l.end);
+                self.g.edge(n, end, "end");
+                let b = self.block(&l.block);
+                self.g.edge(n, b, "body");
+            }
+            ir::Statement::Return(r) => {
+                if let Some(v) = r.val {
+                    let v = self.expr(v);
+                    self.g.edge(n, v, "");
+                }
+                self.refer(n, self.methods.get(&r.func).copied());
+            }
+            ir::Statement::Break(b) => self.refer(n, self.loops.get(&b.for_).copied()),
+            ir::Statement::Continue(c) => self.refer(n, self.loops.get(&c.for_).copied()),
+            ir::Statement::Block(_) => unreachable!(),
+        }
+        n
+    }
+
+    fn location(&mut self, l: &ir::Location) -> usize {
+        let n = self.g.node(&format!("Location {}", self.ir.decl(l.decl).name));
+        if let Some(i) = l.arr_size {
+            let i = self.expr(i);
+            self.g.edge(n, i, "index");
+        }
+        self.refer(n, self.vars.get(&l.decl).copied());
+        n
+    }
+
+    /// edges from the call node `n` to the arguments of `c`
+    fn call_args(&mut self, n: usize, c: &ir::Call) {
+        match c {
+            ir::Call::Method(m) => {
+                for &a in &m.args {
+                    let a = self.expr(a);
+                    self.g.edge(n, a, "");
+                }
+                self.refer(n, self.methods.get(&m.decl).copied());
+            }
+            ir::Call::Callout(c) => {
+                for a in &c.args {
+                    let a = match a {
+                        ir::CalloutArg::Expr(e) => self.expr(*e),
+                        ir::CalloutArg::StringLiteral(s, _) => self.g.node(&format!("StringLiteral {:?}", s)),
+                    };
+                    self.g.edge(n, a, "");
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self, e: ir::ExprId) -> usize {
+        let e = self.ir.expr(e);
+        let kind = match &e.expr {
+            ir::ExprType::Location(l) => return self.location(l),
+            ir::ExprType::Literal(ir::Literal::Int(v)) => format!("Literal {}", v),
+            ir::ExprType::Literal(ir::Literal::Boolean(v)) => format!("Literal {}", v),
+            ir::ExprType::Call(ir::Call::Method(m)) => format!("Call {}", self.ir.method(m.decl).name),
+            ir::ExprType::Call(ir::Call::Callout(c)) => format!("Callout {:?}", c.name),
+            ir::ExprType::Unary(u) => match u.op {
+                ir::UnaryOp::NegInt => String::from("Unary -"),
+                ir::UnaryOp::NegBool => String::from("Unary !"),
+            },
+            ir::ExprType::Binary(b) => format!("Binary {}", printer::binary_op(b.op)),
+            ir::ExprType::Error => String::from("Error"),
+        };
+        let n = self.g.node(&format!("{}\n{}", kind, e.type_));
+        match &e.expr {
+            ir::ExprType::Call(c) => self.call_args(n, c),
+            ir::ExprType::Unary(u) => {
+                let a = self.expr(u.expr);
+                self.g.edge(n, a, "");
+            }
+            ir::ExprType::Binary(b) => {
+                let lhs = self.expr(b.lhs);
+                self.g.edge(n, lhs, "");
+                let rhs = self.expr(b.rhs);
+                self.g.edge(n, rhs, "");
+            }
+            _ => (),
+        }
+        n
+    }
+}
+
+/// IR of `root`, with declarations in rounded boxes and dashed edges
+/// from each use to its declaration
+pub fn ir(root: &ir::IRRoot) -> String {
+    let mut g = IrGraph {
+        g: Graph::new("ir"),
+        ir: root,
+        vars: HashMap::new(),
+        methods: HashMap::new(),
+        loops: HashMap::new(),
+    };
+    let n = g.g.node("Program");
+    for &d in &root.root.field_decls {
+        let d = g.var_decl(d, "FieldDecl");
+        g.g.edge(n, d, "");
+    }
+    for &m in &root.root.method_decls {
+        let m = g.method_decl(m);
+        g.g.edge(n, m, "");
+    }
+    g.g.finish()
+}
+
+/// Control-flow graph of basic blocks of every method, one cluster
+/// each; blocks that cannot be reached are dashed
+pub fn cfg(root: &ir::IRRoot) -> String {
+    let program = tac::lower(root);
+    let mut g = Graph::new("cfg");
+    for f in &program.functions {
+        let cfg = tac::cfg::Cfg::new(f);
+        g.subgraph(&f.name);
+        let nodes: Vec<usize> = cfg
+            .blocks()
+            .iter()
+            .map(|b| {
+                if b.id == cfg.exit() {
+                    return g.node_with("exit", "shape=oval");
+                }
+                let mut label = format!("B{}", b.id);
+                for i in &b.code {
+                    label.push('\n');
+                    label.push_str(&program.instr(i));
+                }
+                g.node_with(&label, if cfg.is_reachable(b.id) { "" } else { "style=dashed" })
+            })
+            .collect();
+        for b in cfg.blocks() {
+            let branch = matches!(b.code.last(), Some(tac::Instr::Branch { .. })) && b.succs.len() == 2;
+            for (i, &s) in b.succs.iter().enumerate() {
+                let label = match (branch, i) {
+                    (false, _) => "",
+                    (true, 0) => "true",
+                    (true, _) => "false",
+                };
+                g.edge(nodes[b.id], nodes[s], label);
+            }
+        }
+        g.close();
+    }
+    g.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::test_util::check;
+
+    /// edges of `dot` as the labels of their ends, and their own label
+    fn edges(dot: &str) -> Vec<(String, String, String)> {
+        let mut labels = HashMap::new();
+        let mut edges = Vec::new();
+        for line in dot.lines().map(str::trim) {
+            let quoted = |s: &str| s.split('"').nth(1).unwrap_or("").to_string();
+            match line.split_once(" -> ") {
+                Some((from, rest)) => {
+                    let to = rest.trim_end_matches(';').split(' ').next().unwrap().to_string();
+                    edges.push((from.to_string(), to, quoted(rest)));
+                }
+                None if line.starts_with('n') => {
+                    labels.insert(line.split(' ').next().unwrap().to_string(), quoted(line));
+                }
+                None => (),
+            }
+        }
+        edges
+            .into_iter()
+            .map(|(from, to, label)| (labels[&from].clone(), labels[&to].clone(), label))
+            .collect()
+    }
+
+    fn edge(from: &str, to: &str, label: &str) -> (String, String, String) {
+        (from.to_string(), to.to_string(), label.to_string())
+    }
+
+    #[test]
+    fn test_ast() {
+        let p = parse("class Program { void main() { x = 'a' + 1; } }").unwrap();
+        assert_eq!(
+            edges(&ast(&p)),
+            vec![
+                edge("Program", "MethodDecl main\\nvoid", ""),
+                edge("Assign =", "Location x", "dst"),
+                edge("Binary +", "Literal 'a'", ""),
+                edge("Binary +", "Literal 1", ""),
+                edge("Assign =", "Binary +", "val"),
+                edge("Block", "Assign =", ""),
+                edge("MethodDecl main\\nvoid", "Block", ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ir() {
+        let ir = check("class Program { int a; void main() { for i = 0, a { break; } } }");
+        let edges = edges(&super::ir(&ir));
+        assert!(edges.contains(&edge("Location a", "FieldDecl a\\nint", "")));
+        assert!(edges.contains(&edge("For i", "Index i\\nint", "index")));
+        assert!(edges.contains(&edge("Break", "For i", "")));
+        assert!(edges.contains(&edge("For i", "Literal 0\\nint", "start")));
+    }
+
+    #[test]
+    fn test_cfg() {
+        let ir = check(
+            "class Program { void main() { int x; \
+             for i = 0, 10 { if (i == 5) { break; } x += i; continue; x = 0; } \
+             return; } }",
+        );
+        let dot = cfg(&ir);
+        // blocks by their name, the first line of their label
+        let edges: Vec<(String, String, String)> = edges(&dot)
+            .into_iter()
+            .map(|(from, to, label)| {
+                let name = |s: String| s.split("\\n").next().unwrap().to_string();
+                (name(from), name(to), label)
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                edge("B0", "B1", ""),
+                edge("B1", "B2", "true"),
+                edge("B1", "B7", "false"),
+                edge("B2", "B3", "true"),
+                edge("B2", "B4", "false"),
+                edge("B3", "B7", ""),
+                edge("B4", "B6", ""),
+                // unreachable, but still flowing to the loop increment
+                edge("B5", "B6", ""),
+                edge("B6", "B1", ""),
+                edge("B7", "exit", ""),
+                edge("B8", "exit", ""),
+            ]
+        );
+        assert!(dot.contains("[label=\"B5\\nx#0 = 0\", style=dashed];"));
+    }
+}
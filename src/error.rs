@@ -0,0 +1,59 @@
+//! errors reported by every compilation stage
+
+use crate::scanner::LexicalError;
+use crate::semantic_analyzer::lints::Warning;
+use crate::semantic_analyzer::SemanticCheckError;
+use std::error::Error;
+use std::fmt;
+
+/// Parse error with the offending token text owned by the error
+pub type ParseError = lalrpop_util::ParseError<usize, String, LexicalError>;
+
+#[derive(Debug)]
+pub enum CompileError {
+    /// malformed token
+    Lexical(LexicalError),
+    /// syntax error
+    Parse(ParseError),
+    /// violation of the decaf semantic rules
+    Semantic(SemanticCheckError),
+    /// lint denied with `-D`
+    Lint(Warning),
+    /// error while generating code
+    Codegen(String),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Lexical(e) => write!(f, "{}", e),
+            Self::Parse(e) => write!(f, "{}", e),
+            Self::Semantic(e) => write!(f, "{}", e),
+            Self::Lint(w) => write!(f, "{}", w),
+            Self::Codegen(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for CompileError {}
+
+impl<T: fmt::Display> From<lalrpop_util::ParseError<usize, T, LexicalError>> for CompileError {
+    fn from(e: lalrpop_util::ParseError<usize, T, LexicalError>) -> Self {
+        match e {
+            lalrpop_util::ParseError::User { error } => Self::Lexical(error),
+            e => Self::Parse(e.map_token(|t| t.to_string())),
+        }
+    }
+}
+
+impl From<LexicalError> for CompileError {
+    fn from(e: LexicalError) -> Self {
+        Self::Lexical(e)
+    }
+}
+
+impl From<SemanticCheckError> for CompileError {
+    fn from(e: SemanticCheckError) -> Self {
+        Self::Semantic(e)
+    }
+}
@@ -0,0 +1,382 @@
+//! canonical pretty-printer for decaf source code
+
+use crate::scanner::Scanner;
+use crate::span::Span;
+use crate::token::*;
+
+const INDENT: &str = "    ";
+
+/// Operator precedence, from the `TExpr0`..`TExpr7` levels of the grammar
+fn precedence(e: &Expr0) -> u8 {
+    match e {
+        Expr0::Location(_) | Expr0::MethodCall(_) | Expr0::Literal(_) => 0,
+        Expr0::Unary(u) => match u.op {
+            UnaryOp::NegInt => 0,
+            UnaryOp::NegBool => 1,
+        },
+        Expr0::Binary(b) => match &b.op {
+            BinaryOp::Arith(ArithOp::Mul | ArithOp::Div | ArithOp::Mod) => 2,
+            BinaryOp::Arith(ArithOp::Add | ArithOp::Sub) => 3,
+            BinaryOp::Compare(_) => 4,
+            BinaryOp::Eq(_) => 5,
+            BinaryOp::Cond(CondOp::And) => 6,
+            BinaryOp::Cond(CondOp::Or) => 7,
+        },
+    }
+}
+
+pub(crate) fn binary_op(op: &BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Arith(ArithOp::Add) => "+",
+        BinaryOp::Arith(ArithOp::Sub) => "-",
+        BinaryOp::Arith(ArithOp::Mul) => "*",
+        BinaryOp::Arith(ArithOp::Div) => "/",
+        BinaryOp::Arith(ArithOp::Mod) => "%",
+        BinaryOp::Compare(CompareOp::LT) => "<",
+        BinaryOp::Compare(CompareOp::GT) => ">",
+        BinaryOp::Compare(CompareOp::LE) => "<=",
+        BinaryOp::Compare(CompareOp::GE) => ">=",
+        BinaryOp::Eq(EqOp::EQ) => "==",
+        BinaryOp::Eq(EqOp::NE) => "!=",
+        BinaryOp::Cond(CondOp::And) => "&&",
+        BinaryOp::Cond(CondOp::Or) => "||",
+    }
+}
+
+pub(crate) fn assign_op(op: &AssignOp) -> &'static str {
+    match op {
+        AssignOp::Assign => "=",
+        AssignOp::AddAssign => "+=",
+        AssignOp::SubAssign => "-=",
+        AssignOp::MulAssign => "*=",
+        AssignOp::DivAssign => "/=",
+    }
+}
+
+pub(crate) fn type_name(t: &Type) -> &'static str {
+    match t {
+        Type::Int => "int",
+        Type::Bool => "boolean",
+        Type::Void => "void",
+    }
+}
+
+/// `c` as written inside a char or string literal; both quotes must be
+/// escaped in either kind of literal
+fn escape(c: char) -> String {
+    match c {
+        '\\' => String::from("\\\\"),
+        '\n' => String::from("\\n"),
+        '\t' => String::from("\\t"),
+        '"' | '\'' => format!("\\{}", c),
+        c => c.to_string(),
+    }
+}
+
+fn string_literal(s: &str) -> String {
+    let body: String = s.chars().map(escape).collect();
+    format!("\"{}\"", body)
+}
+
+/// Expression printed at precedence `level` or tighter
+fn expr(e: &Expr0, level: u8) -> String {
+    let s = match e {
+        Expr0::Location(l) => location(l),
+        Expr0::MethodCall(c) => method_call(c),
+        Expr0::Literal(l) => match &l.kind {
+            LiteralKind::Int(s) => s.clone(),
+            LiteralKind::Bool(BoolLiteral::True) => String::from("true"),
+            LiteralKind::Bool(BoolLiteral::False) => String::from("false"),
+            LiteralKind::Char(c) => format!("'{}'", escape(*c)),
+        },
+        Expr0::Unary(u) => match u.op {
+            UnaryOp::NegInt => format!("-{}", expr(&u.expr, 0)),
+            UnaryOp::NegBool => format!("!{}", expr(&u.expr, 0)),
+        },
+        Expr0::Binary(b) => {
+            let p = precedence(e);
+            format!("{} {} {}", expr(&b.lhs, p), binary_op(&b.op), expr(&b.rhs, p - 1))
+        }
+    };
+    if precedence(e) > level {
+        format!("({})", s)
+    } else {
+        s
+    }
+}
+
+fn location(l: &Location) -> String {
+    match &l.arr_size {
+        Some(i) => format!("{}[{}]", l.name.name, expr(i, 7)),
+        None => l.name.name.clone(),
+    }
+}
+
+fn method_call(c: &MethodCall) -> String {
+    match c {
+        MethodCall::Method(m) => {
+            let args: Vec<String> = m.args.iter().map(|a| expr(a, 7)).collect();
+            format!("{}({})", m.name.name, args.join(", "))
+        }
+        MethodCall::Callout(c) => {
+            let mut args = vec![string_literal(&c.name)];
+            args.extend(c.args.iter().map(|a| match a {
+                CalloutArg::Expr(e) => expr(e, 7),
+                CalloutArg::StringLiteral(s, _) => string_literal(s),
+            }));
+            format!("callout({})", args.join(", "))
+        }
+    }
+}
+
+struct Printer<'a> {
+    src: &'a str,
+    /// comments not printed yet, in source order
+    comments: std::iter::Peekable<std::vec::IntoIter<Span>>,
+    out: String,
+    depth: usize,
+}
+
+impl<'a> Printer<'a> {
+    fn line(&mut self, s: &str) {
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(s);
+    }
+
+    fn blank_line(&mut self) {
+        if !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+        self.out.push('\n');
+    }
+
+    /// print comments starting before `pos` on their own lines
+    fn comments_before(&mut self, pos: usize) {
+        while let Some(c) = self.comments.next_if(|c| c.start < pos) {
+            let text = &self.src[c.start..c.end];
+            self.line(text);
+        }
+    }
+
+    /// print a comment on the same source line after `pos` at the end
+    /// of the current line
+    fn trailing_comment(&mut self, pos: usize) {
+        let src = self.src;
+        if let Some(c) = self
+            .comments
+            .next_if(|c| c.start >= pos && !src[pos..c.start].contains('\n'))
+        {
+            self.out.push(' ');
+            self.out.push_str(&src[c.start..c.end]);
+        }
+    }
+
+    /// print the block at the end of the current line
+    fn block(&mut self, b: &Block) {
+        self.out.push('{');
+        self.trailing_comment(b.span.start + 1);
+        self.depth += 1;
+        for d in &b.var_decls {
+            self.comments_before(d.span.start);
+            let names: Vec<&str> = d.identifiers.iter().map(|i| i.name.as_str()).collect();
+            self.line(&format!("{} {};", type_name(&d.type_), names.join(", ")));
+            self.trailing_comment(d.span.end);
+        }
+        for s in &b.statements {
+            self.statement(s);
+        }
+        self.comments_before(b.span.end);
+        self.depth -= 1;
+        self.line("}");
+    }
+
+    fn statement(&mut self, s: &Statement) {
+        self.comments_before(s.span().start);
+        match s {
+            Statement::Assign(a) => {
+                let line = format!("{} {} {};", location(&a.dst), assign_op(&a.op), expr(&a.val, 7));
+                self.line(&line);
+            }
+            Statement::MethodCall(c) => self.line(&format!("{};", method_call(c))),
+            Statement::IfElse(i) => {
+                self.line(&format!("if ({}) ", expr(&i.cond, 7)));
+                self.block(&i.true_block);
+                if let Some(b) = &i.false_block {
+                    self.out.push_str(" else ");
+                    self.block(b);
+                }
+            }
+            Statement::Loop(l) => {
+                self.line(&format!("for {} = {}, {} ", l.index_var.name, expr(&l.start, 7), expr(&l.end, 7)));
+                self.block(&l.block);
+            }
+            Statement::Return(r) => match &r.val {
+                Some(v) => self.line(&format!("return {};", expr(v, 7))),
+                None => self.line("return;"),
+            },
+            Statement::Break(_) => self.line("break;"),
+            Statement::Continue(_) => self.line("continue;"),
+            Statement::Block(b) => {
+                self.line("");
+                self.block(b);
+            }
+        }
+        self.trailing_comment(s.span().end);
+    }
+
+    fn program(&mut self, p: &Program) {
+        self.comments_before(p.span.start);
+        self.line("class Program {");
+        self.depth += 1;
+        for f in &p.field_decls {
+            self.comments_before(f.span.start);
+            let names: Vec<String> = f
+                .loc
+                .iter()
+                .map(|d| match &d.arr_size {
+                    Some(n) => format!("{}[{}]", d.name.name, n),
+                    None => d.name.name.clone(),
+                })
+                .collect();
+            self.line(&format!("{} {};", type_name(&f.type_), names.join(", ")));
+            self.trailing_comment(f.span.end);
+        }
+        for (i, m) in p.method_decls.iter().enumerate() {
+            if i > 0 || !p.field_decls.is_empty() {
+                self.blank_line();
+            }
+            self.comments_before(m.span.start);
+            let args: Vec<String> = m
+                .args
+                .iter()
+                .map(|a| format!("{} {}", type_name(&a.type_), a.name.name))
+                .collect();
+            self.line(&format!("{} {}({}) ", type_name(&m.return_type), m.name.name, args.join(", ")));
+            self.block(&m.block);
+            self.trailing_comment(m.span.end);
+        }
+        self.comments_before(p.span.end);
+        self.depth -= 1;
+        self.line("}");
+        self.trailing_comment(p.span.end);
+        self.comments_before(self.src.len());
+        self.out.push('\n');
+    }
+}
+
+/// Canonical source code of `p`, keeping the `//` comments of `src`
+pub fn format_program(p: &Program, src: &str) -> String {
+    let mut printer = Printer {
+        src,
+        comments: Scanner::comments(src).into_iter().peekable(),
+        out: String::new(),
+        depth: 0,
+    };
+    printer.program(p);
+    printer.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::scanner::Scanner;
+    use crate::test_util::get_current_dir;
+    use std::fs::{read_dir, read_to_string};
+    use std::path::PathBuf;
+
+    fn format(src: &str) -> String {
+        format_program(&parse(src).unwrap(), src)
+    }
+
+    fn format_expr(e: &str) -> String {
+        let src = format!("class Program {{ void main() {{ x = {}; }} }}", e);
+        let out = format(&src);
+        let line = out.lines().find(|l| l.contains("x = ")).unwrap();
+        line.trim().trim_start_matches("x = ").trim_end_matches(';').to_string()
+    }
+
+    #[test]
+    fn test_parenthesize() {
+        assert_eq!(format_expr("(1 + 2) * 3"), "(1 + 2) * 3");
+        assert_eq!(format_expr("1 + (2 * 3)"), "1 + 2 * 3");
+        assert_eq!(format_expr("(1 - 2) - 3"), "1 - 2 - 3");
+        assert_eq!(format_expr("1 - (2 - 3)"), "1 - (2 - 3)");
+        assert_eq!(format_expr("-(-a[(1)])"), "--a[1]");
+        assert_eq!(format_expr("!(a && b) || (c == (d < e))"), "!(a && b) || c == d < e");
+        assert_eq!(format_expr("a && (b || c)"), "a && (b || c)");
+        assert_eq!(format_expr("-(a * b)"), "-(a * b)");
+    }
+
+    #[test]
+    fn test_format() {
+        let src = "\
+// header
+class Program
+{
+\tint A[10],n; // globals
+  void main ( ) { // entry
+\tint x;
+\t// leading
+\tif(x<2){callout(\"printf\",\"%d \\\"q\\\"\\n\",'\\'');}else{
+\t  // only comment
+\t}
+\tfor i = 0, n { break; }
+\t} // end
+}
+";
+        let expected = "\
+// header
+class Program {
+    int A[10], n; // globals
+
+    void main() { // entry
+        int x;
+        // leading
+        if (x < 2) {
+            callout(\"printf\", \"%d \\\"q\\\"\\n\", '\\'');
+        } else {
+            // only comment
+        }
+        for i = 0, n {
+            break;
+        }
+    } // end
+}
+";
+        assert_eq!(format(src), expected);
+    }
+
+    #[test]
+    fn test_quotes_round_trip() {
+        let src = "class Program { void main() { callout(\"printf\", \"it\\'s \\\"%c\\\"\\n\", '\\\"', '\\''); } }";
+        let out = format(src);
+        assert!(out.contains("\"it\\'s \\\"%c\\\"\\n\", '\\\"', '\\''"), "{}", out);
+        // the formatted program scans and formats to itself
+        assert!(Scanner::tokenize(&out).is_ok());
+        assert_eq!(format(&out), out);
+    }
+
+    #[test]
+    fn test_idempotent() {
+        let root = get_current_dir();
+        let dirs = [
+            ["src", "parser", "testcases", "legal"],
+            ["src", "semantic_analyzer", "testcases", "."],
+        ];
+        for dir in dirs {
+            let dir: PathBuf = std::iter::once(root.as_str()).chain(dir).collect();
+            for entry in read_dir(dir).unwrap() {
+                let src = read_to_string(entry.unwrap().path()).unwrap();
+                let formatted = format(&src);
+                assert_eq!(format(&formatted), formatted);
+                assert_eq!(Scanner::comments(&formatted).len(), Scanner::comments(&src).len());
+            }
+        }
+    }
+}
@@ -1,21 +1,319 @@
 #[macro_use] extern crate lalrpop_util;
 
-mod token;
-mod parser;
-mod semantic_analyzer;
+pub mod span;
+pub mod scanner;
+pub mod token;
+pub mod parser;
+pub mod semantic_analyzer;
+pub mod error;
+pub mod diagnostic;
+pub mod codegen;
+pub mod dot;
+pub mod formatter;
+pub mod tac;
+pub mod visit;
 lalrpop_mod!(#[allow(clippy::all)] decaf);
 
 #[cfg(test)]
 mod test_util;
 
-use parser::DecafParser;
-use semantic_analyzer::SemanticAnalyzer;
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
 
+pub use error::CompileError;
+use scanner::Scanner;
+use semantic_analyzer::lints::{self, Level, LintLevels, Warning};
+use semantic_analyzer::{ir, printer, SemanticAnalyzer};
 
-/// compile decaf source code to x86-64 assembly code
-/// TODO: add compile error code
-pub fn compile(code: &str) -> String {
-    let parsed = DecafParser::new().parse(code).unwrap();
-    let _ir = SemanticAnalyzer::new().create_ir(parsed);
-    String::new()
+/// Compilation stage to stop at (`-target`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Scan,
+    Parse,
+    Inter,
+    Assembly,
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scan" => Ok(Self::Scan),
+            "parse" => Ok(Self::Parse),
+            "inter" => Ok(Self::Inter),
+            "assembly" => Ok(Self::Assembly),
+            _ => Err(format!("unknown target: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Scan => "scan",
+            Self::Parse => "parse",
+            Self::Inter => "inter",
+            Self::Assembly => "assembly",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Graph printed instead of the output of a target (`--emit`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    /// the parsed token tree
+    DotAst,
+    /// the checked IR
+    DotIr,
+    /// the control-flow graph of every method
+    DotCfg,
+    /// the control-flow graph of every method as text, with its analyses
+    Cfg,
+}
+
+impl FromStr for Emit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot-ast" => Ok(Self::DotAst),
+            "dot-ir" => Ok(Self::DotIr),
+            "dot-cfg" => Ok(Self::DotCfg),
+            "cfg" => Ok(Self::Cfg),
+            _ => Err(format!("unknown emit kind: {}", s)),
+        }
+    }
+}
+
+/// Result of the last stage run by [`compile`]
+#[derive(Debug)]
+pub enum Output {
+    /// `-target scan`: token listing
+    Scan(String),
+    /// `-target parse`: the parsed token tree
+    Parse(token::Program),
+    /// `-target inter`: the semantically checked IR
+    Inter(ir::IRRoot),
+    /// `-target assembly`: x86-64 assembly code
+    Assembly(String),
+}
+
+impl Output {
+    /// text to be written to the output file
+    pub fn text(&self) -> Cow<'_, str> {
+        match self {
+            Self::Scan(s) | Self::Assembly(s) => Cow::Borrowed(s),
+            Self::Inter(ir) => Cow::Owned(printer::print_ir(ir)),
+            Self::Parse(_) => Cow::Borrowed(""),
+        }
+    }
+}
+
+/// compile decaf source code up to `target`
+///
+/// Every error of the first failing stage is returned; later stages
+/// are not run, except that the semantic analyzer still checks the
+/// program recovered from syntax errors.
+pub fn compile(code: &str, target: Target) -> Result<Output, Vec<CompileError>> {
+    compile_with_lints(code, target, &LintLevels::default()).0
+}
+
+/// [`compile`], also running the lints on the IR
+///
+/// Lints set to `Warn` are returned as warnings, even if a later stage
+/// fails; denied lints are errors.
+pub fn compile_with_lints(
+    code: &str,
+    target: Target,
+    levels: &LintLevels,
+) -> (Result<Output, Vec<CompileError>>, Vec<Warning>) {
+    let ir = match check(code, target) {
+        Ok(Checked::Output(o)) => return (Ok(o), Vec::new()),
+        Ok(Checked::Ir(ir)) => ir,
+        Err(e) => return (Err(e), Vec::new()),
+    };
+    let (denied, warnings): (Vec<Warning>, Vec<Warning>) = lints::run_lints(&ir, levels)
+        .into_iter()
+        .partition(|w| levels.get(w.lint) == Level::Deny);
+    if !denied.is_empty() {
+        return (Err(denied.into_iter().map(CompileError::Lint).collect()), warnings);
+    }
+    if target == Target::Inter {
+        return (Ok(Output::Inter(ir)), warnings);
+    }
+
+    (Ok(Output::Assembly(codegen::generate(&tac::lower(&ir)))), warnings)
+}
+
+/// Result of the front end: the output of an earlier target, or the IR
+enum Checked {
+    Output(Output),
+    Ir(ir::IRRoot),
+}
+
+/// scan, parse and check `code`, stopping early for an earlier `target`
+fn check(code: &str, target: Target) -> Result<Checked, Vec<CompileError>> {
+    let tokens = Scanner::tokenize(code)
+        .map_err(|errors| errors.into_iter().map(CompileError::from).collect::<Vec<_>>())?;
+    if target == Target::Scan {
+        return Ok(Checked::Output(Output::Scan(scanner::token_listing(code, &tokens))));
+    }
+
+    let (parsed, errors) = parser::parse_tokens(tokens);
+    let mut errors: Vec<CompileError> = errors.into_iter().map(CompileError::from).collect();
+    let parsed = match parsed {
+        Some(p) if target != Target::Parse || errors.is_empty() => p,
+        _ => return Err(errors),
+    };
+    if target == Target::Parse {
+        return Ok(Checked::Output(Output::Parse(parsed)));
+    }
+
+    match SemanticAnalyzer::new().create_ir(parsed) {
+        Ok(ir) if errors.is_empty() => Ok(Checked::Ir(ir)),
+        Ok(_) => Err(errors),
+        Err(e) => {
+            errors.extend(e.into_iter().map(CompileError::from));
+            Err(errors)
+        }
+    }
+}
+
+/// Graphviz dot text, or the text dump for [`Emit::Cfg`], of `emit`
+/// for `code`
+pub fn emit(code: &str, emit: Emit) -> Result<String, Vec<CompileError>> {
+    let target = match emit {
+        Emit::DotAst => Target::Parse,
+        Emit::DotIr | Emit::DotCfg | Emit::Cfg => Target::Inter,
+    };
+    match check(code, target)? {
+        Checked::Output(Output::Parse(p)) => Ok(dot::ast(&p)),
+        Checked::Ir(ir) => Ok(match emit {
+            Emit::DotIr => dot::ir(&ir),
+            Emit::DotCfg => dot::cfg(&ir),
+            _ => tac::cfg::dump(&tac::lower(&ir)),
+        }),
+        Checked::Output(_) => unreachable!(),
+    }
+}
+
+/// canonically formatted decaf source code
+pub fn format(code: &str) -> Result<String, Vec<CompileError>> {
+    let tokens = Scanner::tokenize(code)
+        .map_err(|errors| errors.into_iter().map(CompileError::from).collect::<Vec<_>>())?;
+    match parser::parse_tokens(tokens) {
+        (Some(p), errors) if errors.is_empty() => Ok(formatter::format_program(&p, code)),
+        (_, errors) => Err(errors.into_iter().map(CompileError::from).collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use semantic_analyzer::SemanticCheckError;
+
+    #[test]
+    fn test_target_from_str() {
+        assert_eq!("scan".parse(), Ok(Target::Scan));
+        assert_eq!("assembly".parse(), Ok(Target::Assembly));
+        assert!("codegen".parse::<Target>().is_err());
+    }
+
+    #[test]
+    fn test_compile_stops_at_target() {
+        // semantically illegal: no main method
+        let code = "class Program { void foo() {} }";
+        assert!(matches!(compile(code, Target::Parse), Ok(Output::Parse(_))));
+        let errors = compile(code, Target::Inter).unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [CompileError::Semantic(SemanticCheckError::NoMainMethod { .. })]
+        ));
+        // syntax errors are not reported by the scanner
+        let code = "class Program { void main() { x = ; } }";
+        assert!(compile(code, Target::Scan).is_ok());
+        assert!(compile(code, Target::Parse).is_err());
+        assert!(compile("class Program { # }", Target::Scan).is_err());
+
+        let listing = compile("class Program {\n  int x;\n}", Target::Scan).unwrap();
+        assert_eq!(
+            listing.text(),
+            "1 class\n1 IDENTIFIER Program\n1 {\n2 int\n2 IDENTIFIER x\n2 ;\n3 }\n"
+        );
+    }
+
+    #[test]
+    fn test_compile_reports_syntax_and_semantic_errors() {
+        let code = "class Program { void main() { int x; x = ; x = true; } }";
+        let errors = compile(code, Target::Inter).unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompileError::Parse(_),
+                CompileError::Semantic(SemanticCheckError::AssignTypeMismatch { .. })
+            ]
+        ));
+        assert_eq!(compile(code, Target::Parse).unwrap_err().len(), 1);
+
+        // parameters lost to a malformed signature are not unknown names
+        let code = "class Program { int b; void foo(int x int y) { b = x + y; } void main() { foo(1, 2); b = true; } }";
+        let errors = compile(code, Target::Inter).unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompileError::Parse(_),
+                CompileError::Semantic(SemanticCheckError::AssignTypeMismatch { .. })
+            ]
+        ));
+
+        // the block of a malformed `if` header does not hide later errors
+        let code = "class Program { void main() { int x; if (x { x = 1; } x = true; } }";
+        let errors = compile(code, Target::Inter).unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompileError::Parse(_),
+                CompileError::Semantic(SemanticCheckError::AssignTypeMismatch { .. })
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_emit() {
+        let code = "class Program { void main() { } }";
+        assert!(emit(code, Emit::DotCfg).unwrap().contains("shape=oval"));
+        assert!(emit(code, Emit::Cfg).unwrap().starts_with("main:\nB0  preds: []"));
+        assert!(emit("class Program { }", Emit::Cfg).is_err());
+    }
+
+    #[test]
+    fn test_compile_with_lints() {
+        let code = "class Program { void main() { int x; } }";
+        let mut levels = LintLevels::default();
+        let (res, warnings) = compile_with_lints(code, Target::Inter, &levels);
+        assert!(res.is_ok());
+        assert_eq!(warnings.len(), 1);
+        levels.set(lints::Lint::UnusedVariable, Level::Deny);
+        let (res, warnings) = compile_with_lints(code, Target::Inter, &levels);
+        assert!(matches!(res.unwrap_err()[..], [CompileError::Lint(_)]));
+        assert!(warnings.is_empty());
+        // lints run on the IR only
+        let (_, warnings) = compile_with_lints(code, Target::Parse, &levels);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_compile_does_not_panic() {
+        for code in [
+            "",
+            "class",
+            "class Program { int x; int x; void main() {} }",
+            "class Program { void main() { int x; x = 2147483648; } }",
+        ] {
+            assert!(compile(code, Target::Assembly).is_err());
+        }
+    }
 }
@@ -1,94 +1,356 @@
-use decafc::compile;
-
-fn main() {
-    // TODO: example code
-let code = r#"  
-// a quicksort program.  set the "length" parameter in main() to the
-// desired size of the sorted array.  if you want to sort an array
-// bigger than 100 elements, you'll also need to adjust the declaration
-// of the global array A.
-
-class Program
-{
-    int A[100];
-    int length;
-    
-    int partition(int p, int r) 
-    {
-	int x, i, j, t;
-        int z;
-        
-	x = A[p];
-	i = p - 1;
-	j = r + 1;
-
-  	for z = 0, length * length {
-	  j = j - 1;
-	    for a = 0, length {
-	      if (A[j] <= x) {
-		break;
-	      }
-	      j = j - 1;
-	    }
-
-	    for a = i + 1, length {
-	      if (A[a] >= x) {
-		i = a;
-		break;
-	      }
-	    }
-
-   	    if (i < j) {
-  		t = A[i];
-  		A[i] = A[j];
-  		A[j] = t;
-  	    } else {
- 		return j;
-  	    }
-  	}
-	return -1;
+use decafc::diagnostic::{to_json, Diagnostic, Renderer, SourceFile};
+use decafc::semantic_analyzer::lints::{Level, Lint, LintLevels};
+use decafc::{compile_with_lints, emit as emit_graph, format, Emit, Target};
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: decafc [option | filename ...]
+       decafc fmt [--check] [--error-format=<fmt>] filename ...
+
+fmt rewrites every file in canonical format, or with --check only
+reports the files that are not formatted.
+
+options:
+    -o <outname>         write output to <outname> instead of stdout
+    -target <stage>      stop after <stage>: scan, parse, inter or assembly (default)
+    -opt [opt...]        enable optimizations; a prefix of - excludes one
+    -debug               print debugging information
+    -W <lint>            warn about <lint> (default for every lint)
+    -A <lint>            allow <lint>
+    -D <lint>            deny <lint>, making it an error
+    --error-format=<fmt> print diagnostics as human (default) or json
+    --emit=<graph>       print a Graphviz graph instead: dot-ast, dot-ir or dot-cfg,
+                         or the control-flow graph as text: cfg
+    -h, --help           print this message
+
+lints, or all of them:
+    unused-variable      fields and local variables that are never read
+    unused-parameter     method parameters that are never read
+    unreachable-code     statements after return, break or continue
+    uninitialized-read   local variables read before they are assigned
+    shadowed-global      local names hiding a field
+    unused-method        methods never called from main
+    missing-return       int or boolean methods that can end without a return";
+
+/// known optimizations for `-opt` (`all` enables every one of them)
+const OPTIMIZATIONS: [&str; 0] = [];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorFormat {
+    Human,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Compile,
+    /// reformat the input files, or only check them
+    Fmt { check: bool },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Options {
+    command: Command,
+    output: Option<String>,
+    target: Target,
+    optimizations: Vec<String>,
+    debug: bool,
+    error_format: ErrorFormat,
+    lints: LintLevels,
+    /// graph printed instead of the output of the target
+    emit: Option<Emit>,
+    files: Vec<String>,
+}
+
+impl Options {
+    /// parse command line arguments (without the program name)
+    fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut opts = Options {
+            command: Command::Compile,
+            output: None,
+            target: Target::Assembly,
+            optimizations: Vec::new(),
+            debug: false,
+            error_format: ErrorFormat::Human,
+            lints: LintLevels::default(),
+            emit: None,
+            files: Vec::new(),
+        };
+        let mut args = args.into_iter().peekable();
+        if args.next_if(|a| a == "fmt").is_some() {
+            opts.command = Command::Fmt { check: false };
+        }
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-o" => match args.next() {
+                    Some(o) => opts.output = Some(o),
+                    None => return Err(String::from("-o requires an output filename")),
+                },
+                "-target" => match args.next() {
+                    Some(t) => opts.target = t.parse()?,
+                    None => return Err(String::from("-target requires a stage")),
+                },
+                "-opt" => {
+                    // optimization names run up to the next option or source file
+                    while let Some(o) = args.next_if(|a| !is_option(a) && !is_source_file(a)) {
+                        let name = o.strip_prefix('-').unwrap_or(&o);
+                        if name != "all" && !OPTIMIZATIONS.contains(&name) {
+                            return Err(format!("unknown optimization: {}", name));
+                        }
+                        opts.optimizations.push(o);
+                    }
+                }
+                "-debug" => opts.debug = true,
+                "-W" | "-A" | "-D" => {
+                    let level = match arg.as_str() {
+                        "-W" => Level::Warn,
+                        "-A" => Level::Allow,
+                        _ => Level::Deny,
+                    };
+                    match args.next().as_deref() {
+                        Some("all") => opts.lints.set_all(level),
+                        Some(l) => opts.lints.set(l.parse::<Lint>()?, level),
+                        None => return Err(format!("{} requires a lint name", arg)),
+                    }
+                }
+                "--error-format=human" => opts.error_format = ErrorFormat::Human,
+                "--error-format=json" => opts.error_format = ErrorFormat::Json,
+                _ if arg.starts_with("--emit=") => opts.emit = Some(arg["--emit=".len()..].parse()?),
+                "--check" if opts.command != Command::Compile => opts.command = Command::Fmt { check: true },
+                _ if arg.starts_with('-') => return Err(format!("unknown option: {}", arg)),
+                _ => opts.files.push(arg),
+            }
+        }
+        if opts.files.is_empty() {
+            return Err(String::from("no input files"));
+        }
+        // every program defines `main` and the same local symbols
+        let assembles = opts.command == Command::Compile && opts.emit.is_none() && opts.target == Target::Assembly;
+        if assembles && opts.files.len() > 1 {
+            return Err(String::from("-target assembly takes a single input file"));
+        }
+        Ok(opts)
+    }
+}
+
+fn is_option(arg: &str) -> bool {
+    matches!(arg, "-o" | "-target" | "-opt" | "-debug" | "-W" | "-A" | "-D" | "-h" | "--help")
+        || arg.starts_with("--")
+}
+
+fn is_source_file(arg: &str) -> bool {
+    arg.ends_with(".dcf")
+}
+
+/// read a source file, reporting the error if it cannot be read
+fn read_source(opts: &Options, renderer: &Renderer, file: &str) -> Option<String> {
+    match fs::read_to_string(file) {
+        Ok(c) => Some(c),
+        Err(e) => {
+            let d = Diagnostic::error(format!("cannot read {}: {}", file, e));
+            emit(opts, renderer, &SourceFile::new(file, ""), &d);
+            None
+        }
     }
+}
 
-    void quicksort(int p, int r)
-    {
-  	int q;
-        
-  	if (p < r) {
-  	    q = partition (p, r);
-  	    quicksort (p, q);
-  	    quicksort (q+1, r);
-  	}
+/// print a diagnostic about `file` to stderr
+fn emit(opts: &Options, renderer: &Renderer, file: &SourceFile, d: &Diagnostic) {
+    match opts.error_format {
+        ErrorFormat::Human => eprintln!("{}", renderer.render(file, d)),
+        ErrorFormat::Json => eprintln!("{}", to_json(file, d)),
     }
-    
-    void main() 
-    {
-	int temp;
-        
-	length = 10; // adjust for sort length
-        
-        callout("printf", "creating random array of %d elements\n", length);
-
-        callout("srandom", 17);
-        
-	for i = 0, length {
-            temp = callout("random");
-            A[i] = temp;
+}
+
+/// compile every input file, returning the concatenated output
+fn run(opts: &Options) -> Result<String, ()> {
+    let renderer = Renderer::new(io::stderr().is_terminal());
+    let mut output = String::new();
+    let mut failed = false;
+    for file in &opts.files {
+        let code = match read_source(opts, &renderer, file) {
+            Some(c) => c,
+            None => {
+                failed = true;
+                continue;
+            }
+        };
+        let source = SourceFile::new(file, &code);
+        if let Some(e) = opts.emit {
+            match emit_graph(&code, e) {
+                Ok(g) => output.push_str(&g),
+                Err(errors) => {
+                    for e in &errors {
+                        emit(opts, &renderer, &source, &Diagnostic::from(e));
+                    }
+                    failed = true;
+                }
+            }
+            continue;
+        }
+        let (result, warnings) = compile_with_lints(&code, opts.target, &opts.lints);
+        for w in &warnings {
+            emit(opts, &renderer, &source, &Diagnostic::from(w));
         }
-        
-        callout("printf", "\nbefore sort:\n");
-	for i = 0, length {
-   	    callout ("printf", "%d\n", A[i]); 
+        match result {
+            Ok(o) => {
+                if opts.debug {
+                    eprintln!("decafc: {}: {} ok\n{:#?}", file, opts.target, o);
+                }
+                output.push_str(&o.text());
+            }
+            Err(errors) => {
+                for e in &errors {
+                    emit(opts, &renderer, &source, &Diagnostic::from(e));
+                }
+                failed = true;
+            }
         }
-        
-        quicksort (0, length - 1);
+    }
+    if failed {
+        Err(())
+    } else {
+        Ok(output)
+    }
+}
+
+/// format every input file in place, or check that it is formatted
+fn run_fmt(opts: &Options, check: bool) -> Result<(), ()> {
+    let renderer = Renderer::new(io::stderr().is_terminal());
+    let mut failed = false;
+    for file in &opts.files {
+        let code = match read_source(opts, &renderer, file) {
+            Some(c) => c,
+            None => {
+                failed = true;
+                continue;
+            }
+        };
+        let formatted = match format(&code) {
+            Ok(f) => f,
+            Err(errors) => {
+                let source = SourceFile::new(file, &code);
+                for e in &errors {
+                    emit(opts, &renderer, &source, &Diagnostic::from(e));
+                }
+                failed = true;
+                continue;
+            }
+        };
+        if formatted == code {
+            continue;
+        }
+        if check {
+            eprintln!("decafc: {} is not formatted", file);
+            failed = true;
+        } else if let Err(e) = fs::write(file, formatted) {
+            eprintln!("decafc: cannot write {}: {}", file, e);
+            failed = true;
+        }
+    }
+    if failed {
+        Err(())
+    } else {
+        Ok(())
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let opts = match Options::parse(args) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("decafc: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    if let Command::Fmt { check } = opts.command {
+        return match run_fmt(&opts, check) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(()) => ExitCode::FAILURE,
+        };
+    }
 
-        callout("printf", "\nafter sort\n");
-	for i = 0, length {
-	  callout ("printf", "%d\n", A[i]); 
-  	}
+    let output = match run(&opts) {
+        Ok(o) => o,
+        Err(()) => return ExitCode::FAILURE,
+    };
+    let written = match &opts.output {
+        Some(path) => fs::write(path, output),
+        None => io::stdout().write_all(output.as_bytes()),
+    };
+    if let Err(e) = written {
+        eprintln!("decafc: cannot write output: {}", e);
+        return ExitCode::FAILURE;
     }
+    ExitCode::SUCCESS
 }
-"#;    
-    let code = String::from(code);
-    compile(&code);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn test_options() {
+        let opts = parse(&["-target", "inter", "-o", "out.s", "-debug", "a.dcf", "b.dcf"]).unwrap();
+        assert_eq!(opts.target, Target::Inter);
+        assert_eq!(opts.output.as_deref(), Some("out.s"));
+        assert!(opts.debug);
+        assert_eq!(opts.error_format, ErrorFormat::Human);
+        assert_eq!(opts.files, vec!["a.dcf", "b.dcf"]);
+
+        let opts = parse(&["--error-format=json", "a.dcf"]).unwrap();
+        assert_eq!(opts.error_format, ErrorFormat::Json);
+
+        let opts = parse(&["-opt", "all", "a.dcf"]).unwrap();
+        assert_eq!(opts.target, Target::Assembly);
+        assert_eq!(opts.optimizations, vec!["all"]);
+
+        let opts = parse(&["-D", "all", "-A", "unused-method", "a.dcf"]).unwrap();
+        assert_eq!(opts.lints.get(Lint::UnusedVariable), Level::Deny);
+        assert_eq!(opts.lints.get(Lint::UnusedMethod), Level::Allow);
+        assert_eq!(opts.files, vec!["a.dcf"]);
+
+        let opts = parse(&["--emit=dot-cfg", "a.dcf"]).unwrap();
+        assert_eq!(opts.emit, Some(Emit::DotCfg));
+        let opts = parse(&["--emit=cfg", "a.dcf"]).unwrap();
+        assert_eq!(opts.emit, Some(Emit::Cfg));
+
+        let opts = parse(&["--emit=dot-ir", "a.dcf", "b.dcf"]).unwrap();
+        assert_eq!(opts.files, vec!["a.dcf", "b.dcf"]);
+
+        let opts = parse(&["fmt", "a.dcf"]).unwrap();
+        assert_eq!(opts.command, Command::Fmt { check: false });
+        let opts = parse(&["fmt", "--check", "a.dcf"]).unwrap();
+        assert_eq!(opts.command, Command::Fmt { check: true });
+        assert_eq!(opts.files, vec!["a.dcf"]);
+    }
+
+    #[test]
+    fn test_options_errors() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["-target", "codegen", "a.dcf"]).is_err());
+        assert!(parse(&["-o"]).is_err());
+        assert!(parse(&["-opt", "cse", "a.dcf"]).is_err());
+        assert!(parse(&["-foo", "a.dcf"]).is_err());
+        assert!(parse(&["--error-format=xml", "a.dcf"]).is_err());
+        assert!(parse(&["--check", "a.dcf"]).is_err());
+        assert!(parse(&["--emit=dot", "a.dcf"]).is_err());
+        assert!(parse(&["-W", "unused", "a.dcf"]).is_err());
+        assert!(parse(&["a.dcf", "-D"]).is_err());
+        assert!(parse(&["fmt"]).is_err());
+        assert!(parse(&["a.dcf", "b.dcf", "-o", "two.s"]).is_err());
+        assert!(parse(&["-target", "assembly", "a.dcf", "b.dcf"]).is_err());
+    }
 }
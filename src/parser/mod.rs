@@ -1,12 +1,53 @@
 use crate::decaf::TProgramParser;
+use crate::scanner::{LexicalError, Scanner, Spanned, Token, TokenKind};
+use crate::token::Program;
+
 pub type DecafParser = TProgramParser;
+pub type ParseError = lalrpop_util::ParseError<usize, TokenKind, LexicalError>;
+
+fn parse_spanned<I>(tokens: I) -> (Option<Program>, Vec<ParseError>)
+where
+    I: IntoIterator<Item = Result<Spanned, LexicalError>>,
+{
+    let mut recovered = Vec::new();
+    let program = DecafParser::new().parse(&mut recovered, tokens);
+    let mut errors: Vec<ParseError> = recovered.into_iter().map(|e| e.error).collect();
+    match program {
+        Ok(p) => (Some(p), errors),
+        Err(e) => {
+            errors.push(e);
+            (None, errors)
+        }
+    }
+}
+
+/// scan and parse decaf source code, returning every syntax error
+pub fn parse(code: &str) -> Result<Program, Vec<ParseError>> {
+    match parse_spanned(Scanner::new(code).map(|t| t.map(Token::into_spanned))) {
+        (Some(p), errors) if errors.is_empty() => Ok(p),
+        (_, errors) => Err(errors),
+    }
+}
+
+/// parse tokens returned by the scanner, recovering from syntax errors
+///
+/// Malformed statements and field declarations are left out of the
+/// program, a block is cut short at its `}` by an error, and a method
+/// with a malformed signature keeps the parameters parsed before the
+/// error and is marked as recovered. The program is `None` if the
+/// parser could not recover.
+pub fn parse_tokens(tokens: Vec<Token>) -> (Option<Program>, Vec<ParseError>) {
+    parse_spanned(tokens.into_iter().map(|t| Ok(t.into_spanned())))
+}
 
 #[cfg(test)]
 mod tests {
     use std::fs::read_to_string;
     use std::path::PathBuf;
-    use std::env;
-    use super::DecafParser;
+    use super::{parse, parse_tokens};
+    use crate::scanner::Scanner;
+    use crate::span::Span;
+    use crate::token::{Expr0, Statement};
     use crate::test_util::get_current_dir;
     
 macro_rules! test_parser_illegal {
@@ -17,7 +58,7 @@ macro_rules! test_parser_illegal {
             let path = get_current_dir();
             let path: PathBuf = [&path, "src", "parser", "testcases", "illegal", $filename].iter().collect();
             let s = read_to_string(&path).unwrap();
-            let program = DecafParser::new().parse(&s);
+            let program = parse(&s);
             assert!(program.is_err());
             
         }
@@ -32,7 +73,7 @@ macro_rules! test_parser_legal {
             let path = get_current_dir();
             let path: PathBuf = [&path, "src", "parser", "testcases", "legal", $filename].iter().collect();
             let s = read_to_string(&path).unwrap();
-            let program = DecafParser::new().parse(&s);
+            let program = parse(&s);
             assert!(program.is_ok());
         }
     };
@@ -41,9 +82,81 @@ macro_rules! test_parser_legal {
     
     #[test]
     fn test_empty() {
-        assert!(DecafParser::new().parse("").is_err());
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn test_span() {
+        let s = "class Program {\n  int a[10];\n  void main() {\n    a[1] = 2 + foo(3);\n    break;\n  }\n}\n";
+        let program = parse(s).unwrap();
+        let text = |span: Span| &s[span.start..span.end];
+
+        assert_eq!(text(program.span), s.trim_end());
+        assert_eq!(text(program.field_decls[0].span), "int a[10];");
+        assert_eq!(text(program.field_decls[0].loc[0].span), "a[10]");
+        let main = &program.method_decls[0];
+        assert_eq!(text(main.name.span), "main");
+        assert!(text(main.span).starts_with("void main() {"));
+        assert!(text(main.block.span).ends_with("  }"));
+
+        let assign = match &main.block.statements[0] {
+            Statement::Assign(a) => a,
+            s => panic!("unexpected statement {:?}", s),
+        };
+        assert_eq!(text(assign.span), "a[1] = 2 + foo(3);");
+        assert_eq!(text(assign.dst.span), "a[1]");
+        assert_eq!(text(assign.val.span()), "2 + foo(3)");
+        match &*assign.val {
+            Expr0::Binary(b) => {
+                assert_eq!(text(b.lhs.span()), "2");
+                assert_eq!(text(b.rhs.span()), "foo(3)");
+            }
+            e => panic!("unexpected expression {:?}", e),
+        }
+        assert_eq!(text(main.block.statements[1].span()), "break;");
     }
     
+    #[test]
+    fn test_error_recovery() {
+        let s = "class Program {\n  int a[;\n  int b;\n  void foo(int x int y) { b = 1; }\n  void main() {\n    b = 1 +;\n    b = (2;\n    b = 3;\n  }\n}\n";
+        assert_eq!(parse(s).unwrap_err().len(), 4);
+
+        let (program, errors) = parse_tokens(Scanner::tokenize(s).unwrap());
+        assert_eq!(errors.len(), 4);
+        let program = program.unwrap();
+        assert_eq!(program.field_decls.len(), 1);
+        assert_eq!(program.field_decls[0].loc[0].name.name, "b");
+        let foo = &program.method_decls[0];
+        // the parameters before the error are kept
+        assert_eq!(foo.args.len(), 1);
+        assert_eq!(foo.args[0].name.name, "x");
+        assert!(foo.recovered);
+        assert_eq!(foo.block.statements.len(), 1);
+        assert_eq!(program.method_decls[1].block.statements.len(), 1);
+
+        // a missing `;` before `}` ends the block, not the enclosing ones
+        let s = "class Program {\n  int f() { int y; y = 1 }\n  void main() { y = 2; }\n}\n";
+        let (program, errors) = parse_tokens(Scanner::tokenize(s).unwrap());
+        assert_eq!(errors.len(), 1);
+        let program = program.unwrap();
+        assert_eq!(program.method_decls.len(), 2);
+        assert!(program.method_decls[0].block.statements.is_empty());
+        assert_eq!(program.method_decls[1].block.statements.len(), 1);
+
+        // a malformed statement header is skipped with its block
+        let s = "class Program { void main() { if (x { x = 1; } x = true; } }";
+        let (program, errors) = parse_tokens(Scanner::tokenize(s).unwrap());
+        assert_eq!(errors.len(), 1);
+        let main = &program.unwrap().method_decls[0];
+        assert_eq!(main.block.statements.len(), 1);
+        assert!(matches!(main.block.statements[0], Statement::Assign(_)));
+
+        // no partial program if the parser cannot recover
+        let (program, errors) = parse_tokens(Scanner::tokenize("class Program { void main() { b = 1").unwrap());
+        assert!(program.is_none());
+        assert_eq!(errors.len(), 1);
+    }
+
     test_parser_legal!(test_legal_01, "legal-01");
     test_parser_legal!(test_legal_02, "legal-02");
     test_parser_legal!(test_legal_03, "legal-03");
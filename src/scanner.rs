@@ -0,0 +1,664 @@
+//! Scanner: splits decaf source code into tokens
+
+use crate::span::Span;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    // keywords
+    Boolean,
+    Break,
+    Callout,
+    Class,
+    Continue,
+    Else,
+    For,
+    If,
+    Int,
+    Return,
+    Void,
+
+    // literals
+    Identifier(String),
+    /// decimal or hex literal as written in the source
+    IntLiteral(String),
+    CharLiteral(char),
+    StringLiteral(String),
+    BoolLiteral(bool),
+
+    // punctuation
+    LBrace,
+    RBrace,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    Semicolon,
+
+    // operators
+    Assign,
+    AddAssign,
+    SubAssign,
+    MulAssign,
+    DivAssign,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    LT,
+    GT,
+    LE,
+    GE,
+    EQ,
+    NE,
+    And,
+    Or,
+    Not,
+}
+
+impl TokenKind {
+    fn keyword(s: &str) -> Option<Self> {
+        let k = match s {
+            "boolean" => Self::Boolean,
+            "break" => Self::Break,
+            "callout" => Self::Callout,
+            "class" => Self::Class,
+            "continue" => Self::Continue,
+            "else" => Self::Else,
+            "for" => Self::For,
+            "if" => Self::If,
+            "int" => Self::Int,
+            "return" => Self::Return,
+            "void" => Self::Void,
+            "true" => Self::BoolLiteral(true),
+            "false" => Self::BoolLiteral(false),
+            _ => return None,
+        };
+        Some(k)
+    }
+
+    /// token class printed by `-target scan`, if the token is not
+    /// a keyword or punctuation
+    pub fn class_name(&self) -> Option<&'static str> {
+        match self {
+            Self::Identifier(_) => Some("IDENTIFIER"),
+            Self::IntLiteral(_) => Some("INTLITERAL"),
+            Self::CharLiteral(_) => Some("CHARLITERAL"),
+            Self::StringLiteral(_) => Some("STRINGLITERAL"),
+            Self::BoolLiteral(_) => Some("BOOLEANLITERAL"),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Boolean => "boolean",
+            Self::Break => "break",
+            Self::Callout => "callout",
+            Self::Class => "class",
+            Self::Continue => "continue",
+            Self::Else => "else",
+            Self::For => "for",
+            Self::If => "if",
+            Self::Int => "int",
+            Self::Return => "return",
+            Self::Void => "void",
+            Self::Identifier(s) => s,
+            Self::IntLiteral(s) => s,
+            Self::CharLiteral(c) => return write!(f, "{:?}", c),
+            Self::StringLiteral(s) => return write!(f, "{:?}", s),
+            Self::BoolLiteral(b) => return write!(f, "{}", b),
+            Self::LBrace => "{",
+            Self::RBrace => "}",
+            Self::LParen => "(",
+            Self::RParen => ")",
+            Self::LBracket => "[",
+            Self::RBracket => "]",
+            Self::Comma => ",",
+            Self::Semicolon => ";",
+            Self::Assign => "=",
+            Self::AddAssign => "+=",
+            Self::SubAssign => "-=",
+            Self::MulAssign => "*=",
+            Self::DivAssign => "/=",
+            Self::Plus => "+",
+            Self::Minus => "-",
+            Self::Star => "*",
+            Self::Slash => "/",
+            Self::Percent => "%",
+            Self::LT => "<",
+            Self::GT => ">",
+            Self::LE => "<=",
+            Self::GE => ">=",
+            Self::EQ => "==",
+            Self::NE => "!=",
+            Self::And => "&&",
+            Self::Or => "||",
+            Self::Not => "!",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+    /// 1-based line where the token starts
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexicalErrorKind {
+    UnexpectedChar(char),
+    UnterminatedCharLiteral,
+    EmptyCharLiteral,
+    UnterminatedStringLiteral,
+    /// backslash followed by a character other than `" ' \ t n`
+    UnknownEscape(char),
+    MissingHexDigits,
+    /// character of a literal outside printable ASCII, or a quote or
+    /// backslash that is not escaped
+    InvalidCharInLiteral(char),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexicalError {
+    pub kind: LexicalErrorKind,
+    pub span: Span,
+}
+
+impl fmt::Display for LexicalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            LexicalErrorKind::UnexpectedChar(c) => write!(f, "unexpected character {:?}", c),
+            LexicalErrorKind::UnterminatedCharLiteral => write!(f, "unterminated char literal"),
+            LexicalErrorKind::EmptyCharLiteral => write!(f, "empty char literal"),
+            LexicalErrorKind::UnterminatedStringLiteral => write!(f, "unterminated string literal"),
+            LexicalErrorKind::UnknownEscape(c) => write!(f, "unknown character escape `\\{}`", c),
+            LexicalErrorKind::MissingHexDigits => write!(f, "missing digits after `0x`"),
+            LexicalErrorKind::InvalidCharInLiteral(c) => write!(f, "invalid character {:?} in literal", c),
+        }
+    }
+}
+
+/// Token in the form consumed by the parser
+pub type Spanned = (usize, TokenKind, usize);
+
+impl Token {
+    pub fn into_spanned(self) -> Spanned {
+        (self.span.start, self.kind, self.span.end)
+    }
+}
+
+/// Iterator over the tokens of a source code
+///
+/// Malformed tokens are returned as errors and scanning continues
+/// after them.
+pub struct Scanner<'input> {
+    src: &'input str,
+    chars: Peekable<CharIndices<'input>>,
+    line: usize,
+    /// `//` comments skipped so far
+    comments: Vec<Span>,
+}
+
+impl<'input> Scanner<'input> {
+    pub fn new(src: &'input str) -> Self {
+        Self {
+            src,
+            chars: src.char_indices().peekable(),
+            line: 1,
+            comments: Vec::new(),
+        }
+    }
+
+    /// spans of every `//` comment, up to the end of line
+    pub fn comments(src: &str) -> Vec<Span> {
+        let mut scanner = Scanner::new(src);
+        scanner.by_ref().for_each(drop);
+        scanner.comments
+    }
+
+    /// scan whole source code, returning every token or every error
+    pub fn tokenize(src: &str) -> Result<Vec<Token>, Vec<LexicalError>> {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        for t in Scanner::new(src) {
+            match t {
+                Ok(t) => tokens.push(t),
+                Err(e) => errors.push(e),
+            }
+        }
+        if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn offset(&mut self) -> usize {
+        match self.chars.peek() {
+            Some(&(i, _)) => i,
+            None => self.src.len(),
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next().map(|(_, c)| c);
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.chars.peek().map(|&(_, p)| p) == Some(c) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_while(&mut self, f: impl Fn(char) -> bool) {
+        while let Some(&(_, c)) = self.chars.peek() {
+            if !f(c) {
+                break;
+            }
+            self.bump();
+        }
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        loop {
+            self.eat_while(|c| matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0c'));
+            if self.src[self.offset()..].starts_with("//") {
+                let start = self.offset();
+                self.eat_while(|c| c != '\n');
+                let end = start + self.src[start..self.offset()].trim_end().len();
+                self.comments.push(Span::new(start, end));
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// decoded character of an escape sequence, after its backslash
+    fn scan_escape(&mut self, unterminated: LexicalErrorKind) -> Result<char, LexicalErrorKind> {
+        let c = match self.chars.peek() {
+            Some(&(_, '\n')) | None => return Err(unterminated),
+            Some(&(_, c)) => c,
+        };
+        self.bump();
+        match c {
+            '"' | '\'' | '\\' => Ok(c),
+            't' => Ok('\t'),
+            'n' => Ok('\n'),
+            c => Err(LexicalErrorKind::UnknownEscape(c)),
+        }
+    }
+
+    /// `c` can appear unescaped in a char or string literal
+    fn is_literal_char(c: char) -> bool {
+        matches!(c, ' '..='~') && !matches!(c, '"' | '\'' | '\\')
+    }
+
+    /// skip the rest of a malformed literal, up to its closing quote
+    /// or the end of line
+    fn recover_literal(&mut self, quote: char) {
+        while let Some(&(_, c)) = self.chars.peek() {
+            if c == '\n' {
+                break;
+            }
+            self.bump();
+            if c == quote {
+                break;
+            }
+            if c == '\\' && self.chars.peek().map(|&(_, c)| c) != Some('\n') {
+                self.bump();
+            }
+        }
+    }
+
+    fn scan_char_literal(&mut self) -> Result<TokenKind, LexicalErrorKind> {
+        let c = match self.chars.peek() {
+            Some(&(_, '\'')) => {
+                self.bump();
+                return Err(LexicalErrorKind::EmptyCharLiteral);
+            }
+            Some(&(_, '\n')) | None => return Err(LexicalErrorKind::UnterminatedCharLiteral),
+            Some(&(_, '\\')) => {
+                self.bump();
+                match self.scan_escape(LexicalErrorKind::UnterminatedCharLiteral) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        self.recover_literal('\'');
+                        return Err(e);
+                    }
+                }
+            }
+            Some(&(_, c)) => {
+                self.bump();
+                if !Self::is_literal_char(c) {
+                    self.recover_literal('\'');
+                    return Err(LexicalErrorKind::InvalidCharInLiteral(c));
+                }
+                c
+            }
+        };
+        if self.eat('\'') {
+            Ok(TokenKind::CharLiteral(c))
+        } else {
+            Err(LexicalErrorKind::UnterminatedCharLiteral)
+        }
+    }
+
+    /// string literal up to the first unescaped `"` on the same line
+    fn scan_string_literal(&mut self) -> Result<TokenKind, LexicalErrorKind> {
+        let mut value = String::new();
+        loop {
+            let c = match self.chars.peek() {
+                Some(&(_, '\n')) | None => return Err(LexicalErrorKind::UnterminatedStringLiteral),
+                Some(&(_, c)) => c,
+            };
+            self.bump();
+            match c {
+                '"' => return Ok(TokenKind::StringLiteral(value)),
+                '\\' => match self.scan_escape(LexicalErrorKind::UnterminatedStringLiteral) {
+                    Ok(c) => value.push(c),
+                    Err(e) => {
+                        self.recover_literal('"');
+                        return Err(e);
+                    }
+                },
+                c if Self::is_literal_char(c) => value.push(c),
+                c => {
+                    self.recover_literal('"');
+                    return Err(LexicalErrorKind::InvalidCharInLiteral(c));
+                }
+            }
+        }
+    }
+
+    fn scan_int_literal(&mut self, start: usize, first: char) -> Result<TokenKind, LexicalErrorKind> {
+        if first == '0' && self.eat('x') {
+            let digits = self.offset();
+            self.eat_while(|c| c.is_ascii_hexdigit());
+            if self.offset() == digits {
+                return Err(LexicalErrorKind::MissingHexDigits);
+            }
+        } else {
+            self.eat_while(|c| c.is_ascii_digit());
+        }
+        let end = self.offset();
+        Ok(TokenKind::IntLiteral(self.src[start..end].to_string()))
+    }
+
+    /// operator starting with `c`, which may be followed by `=`
+    fn scan_operator(&mut self, c: char) -> Option<TokenKind> {
+        let with_eq = |k: TokenKind, eq: TokenKind, s: &mut Self| if s.eat('=') { eq } else { k };
+        let k = match c {
+            '{' => TokenKind::LBrace,
+            '}' => TokenKind::RBrace,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            '[' => TokenKind::LBracket,
+            ']' => TokenKind::RBracket,
+            ',' => TokenKind::Comma,
+            ';' => TokenKind::Semicolon,
+            '%' => TokenKind::Percent,
+            '+' => with_eq(TokenKind::Plus, TokenKind::AddAssign, self),
+            '-' => with_eq(TokenKind::Minus, TokenKind::SubAssign, self),
+            '*' => with_eq(TokenKind::Star, TokenKind::MulAssign, self),
+            '/' => with_eq(TokenKind::Slash, TokenKind::DivAssign, self),
+            '<' => with_eq(TokenKind::LT, TokenKind::LE, self),
+            '>' => with_eq(TokenKind::GT, TokenKind::GE, self),
+            '=' => with_eq(TokenKind::Assign, TokenKind::EQ, self),
+            '!' => with_eq(TokenKind::Not, TokenKind::NE, self),
+            '&' if self.eat('&') => TokenKind::And,
+            '|' if self.eat('|') => TokenKind::Or,
+            _ => return None,
+        };
+        Some(k)
+    }
+}
+
+impl<'input> Iterator for Scanner<'input> {
+    type Item = Result<Token, LexicalError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.skip_whitespace_and_comments();
+        let line = self.line;
+        let (start, c) = self.chars.next()?;
+        let kind = match c {
+            'a'..='z' | 'A'..='Z' | '_' => {
+                self.eat_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                let s = &self.src[start..self.offset()];
+                Ok(TokenKind::keyword(s).unwrap_or_else(|| TokenKind::Identifier(s.to_string())))
+            }
+            '0'..='9' => self.scan_int_literal(start, c),
+            '\'' => self.scan_char_literal(),
+            '"' => self.scan_string_literal(),
+            c => self.scan_operator(c).ok_or(LexicalErrorKind::UnexpectedChar(c)),
+        };
+        let span = Span::new(start, self.offset());
+        Some(match kind {
+            Ok(kind) => Ok(Token { kind, span, line }),
+            Err(kind) => Err(LexicalError { kind, span }),
+        })
+    }
+}
+
+/// Token listing in the format of `-target scan`
+pub fn token_listing(src: &str, tokens: &[Token]) -> String {
+    let mut out = String::new();
+    for t in tokens {
+        let text = &src[t.span.start..t.span.end];
+        let line = match t.kind.class_name() {
+            Some(class) => format!("{} {} {}\n", t.line, class, text),
+            None => format!("{} {}\n", t.line, text),
+        };
+        out.push_str(&line);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(src: &str) -> Vec<TokenKind> {
+        Scanner::tokenize(src)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.kind)
+            .collect()
+    }
+
+    fn error(src: &str) -> LexicalErrorKind {
+        let errors = Scanner::tokenize(src).unwrap_err();
+        errors[0].kind.clone()
+    }
+
+    #[test]
+    fn test_keywords_and_identifiers() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("class Program { boolean callout_ if1 true false }"),
+            vec![
+                Class,
+                Identifier(String::from("Program")),
+                LBrace,
+                Boolean,
+                Identifier(String::from("callout_")),
+                Identifier(String::from("if1")),
+                BoolLiteral(true),
+                BoolLiteral(false),
+                RBrace,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_operators() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("+ += - -= * *= / /= % < <= > >= = == ! != && ||"),
+            vec![
+                Plus, AddAssign, Minus, SubAssign, Star, MulAssign, Slash, DivAssign, Percent, LT, LE,
+                GT, GE, Assign, EQ, Not, NE, And, Or,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds(r#"12 0x1F 'a' "hi" "#),
+            vec![
+                IntLiteral(String::from("12")),
+                IntLiteral(String::from("0x1F")),
+                CharLiteral('a'),
+                StringLiteral(String::from("hi")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hex_literals() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("0x0 0xdeadBEEF 0x7fffffff 0x1fz"),
+            vec![
+                IntLiteral(String::from("0x0")),
+                IntLiteral(String::from("0xdeadBEEF")),
+                IntLiteral(String::from("0x7fffffff")),
+                IntLiteral(String::from("0x1f")),
+                Identifier(String::from("z")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_char_escapes() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds(r#"'\"' '\'' '\\' '\t' '\n' ' ' '~'"#),
+            vec![
+                CharLiteral('"'),
+                CharLiteral('\''),
+                CharLiteral('\\'),
+                CharLiteral('\t'),
+                CharLiteral('\n'),
+                CharLiteral(' '),
+                CharLiteral('~'),
+            ]
+        );
+        // quotes must be escaped, and other characters printable ASCII
+        assert_eq!(error(r#"'"'"#), LexicalErrorKind::InvalidCharInLiteral('"'));
+        assert_eq!(error("'\t'"), LexicalErrorKind::InvalidCharInLiteral('\t'));
+        assert_eq!(error("'é'"), LexicalErrorKind::InvalidCharInLiteral('é'));
+        let errors = Scanner::tokenize("'\u{7f}' x").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span, Span::new(0, 3));
+    }
+
+    #[test]
+    fn test_string_literals() {
+        use TokenKind::*;
+        // a string ends at the first unescaped quote
+        assert_eq!(
+            kinds(r#""a" + "b""#),
+            vec![
+                StringLiteral(String::from("a")),
+                Plus,
+                StringLiteral(String::from("b")),
+            ]
+        );
+        assert_eq!(
+            kinds(r#""say \"hi\"\n" "it\'s\t\\""#),
+            vec![
+                StringLiteral(String::from("say \"hi\"\n")),
+                StringLiteral(String::from("it's\t\\")),
+            ]
+        );
+        assert_eq!(error(r#""it's""#), LexicalErrorKind::InvalidCharInLiteral('\''));
+        assert_eq!(error("\"a\tb\""), LexicalErrorKind::InvalidCharInLiteral('\t'));
+        assert_eq!(error("\"é\""), LexicalErrorKind::InvalidCharInLiteral('é'));
+        // the rest of the string is skipped
+        let errors = Scanner::tokenize("\"a\tb\" x").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span, Span::new(0, 5));
+        // the listing shows the literal as written
+        let src = r#""%d\n""#;
+        let tokens = Scanner::tokenize(src).unwrap();
+        assert_eq!(token_listing(src, &tokens), "1 STRINGLITERAL \"%d\\n\"\n");
+    }
+
+    #[test]
+    fn test_unknown_escapes() {
+        assert_eq!(error(r"'\a'"), LexicalErrorKind::UnknownEscape('a'));
+        assert_eq!(error(r#""a\qb""#), LexicalErrorKind::UnknownEscape('q'));
+        assert_eq!(error("\"a\\"), LexicalErrorKind::UnterminatedStringLiteral);
+
+        // the rest of the literal is skipped
+        let errors = Scanner::tokenize(r#"x = "a\q \" b"; y"#).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span, Span::new(4, 14));
+    }
+
+    #[test]
+    fn test_comments_and_lines() {
+        let tokens = Scanner::tokenize("a // comment b\n\n  c").unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].line, 1);
+        assert_eq!(tokens[1].line, 3);
+        assert_eq!(tokens[1].span, Span::new(18, 19));
+        assert_eq!(Scanner::comments("a // comment b\n\n  c"), vec![Span::new(2, 14)]);
+    }
+
+    #[test]
+    fn test_malformed_tokens() {
+        assert_eq!(error("a # b"), LexicalErrorKind::UnexpectedChar('#'));
+        assert_eq!(error("a & b"), LexicalErrorKind::UnexpectedChar('&'));
+        assert_eq!(error("''"), LexicalErrorKind::EmptyCharLiteral);
+        assert_eq!(error("'ab'"), LexicalErrorKind::UnterminatedCharLiteral);
+        assert_eq!(error("\"abc\n\""), LexicalErrorKind::UnterminatedStringLiteral);
+        assert_eq!(error("0x"), LexicalErrorKind::MissingHexDigits);
+
+        // scanning continues after an error
+        let errors = Scanner::tokenize("#\n  x $").unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[1].span, Span::new(6, 7));
+    }
+
+    #[test]
+    fn test_token_listing() {
+        let src = "class Program {\n  int a;\n  a = 'c' + 0x1;\n}";
+        let tokens = Scanner::tokenize(src).unwrap();
+        let expected = "\
+1 class
+1 IDENTIFIER Program
+1 {
+2 int
+2 IDENTIFIER a
+2 ;
+3 IDENTIFIER a
+3 =
+3 CHARLITERAL 'c'
+3 +
+3 INTLITERAL 0x1
+3 ;
+4 }
+";
+        assert_eq!(token_listing(src, &tokens), expected);
+    }
+}
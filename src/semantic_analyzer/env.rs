@@ -1,28 +1,41 @@
-use super::ir::{For, IfElse, MethodDecl, VarDecl};
+use super::ir::{DeclId, IRRoot, MethodId, StmtId};
+use crate::span::Span;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::mem::discriminant;
 use std::rc::Rc;
 
+#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub enum EnvType {
     Global,
     Anon,
-    Method(MethodDecl),
-    For(For),
-    If(IfElse),
-    Else(IfElse),
+    Method(MethodId),
+    /// the `for` statement being built
+    For(StmtId),
+    If,
+    Else,
     NoEnv,
 }
 
 pub enum EnvError {
-    DuplicatedMethod(MethodDecl),
-    DuplicatedVar(VarDecl),
+    DuplicatedMethod(MethodId),
+    DuplicatedVar(DeclId),
+}
+
+impl EnvError {
+    /// span of the previous declaration
+    pub fn prev_span(&self, ir: &IRRoot) -> Span {
+        match *self {
+            Self::DuplicatedMethod(m) => ir.method(m).span,
+            Self::DuplicatedVar(v) => ir.decl(v).span,
+        }
+    }
 }
 
 struct Env {
     pub type_: EnvType,
-    pub table: HashMap<String, VarDecl>,
+    pub table: HashMap<String, DeclId>,
 }
 
 impl Env {
@@ -60,32 +73,38 @@ impl EnvContext {
         Self { envs, t }
     }
 
-    /// Add a new var declation in current env
-    pub fn add_var(&self, f: &VarDecl) -> Result<(), EnvError> {
-        self.envs.borrow_mut().add_var(f)
+    /// Add a new var declation named `name` in current env
+    pub fn add_var(&self, name: &str, d: DeclId) -> Result<(), EnvError> {
+        self.envs.borrow_mut().add_var(name, d)
     }
 
-    /// Add a new method
-    pub fn add_method(&self, m: &MethodDecl) -> Result<(), EnvError> {
-        self.envs.borrow_mut().add_method(m)
+    /// Add a new method named `name`
+    pub fn add_method(&self, name: &str, m: MethodId) -> Result<(), EnvError> {
+        self.envs.borrow_mut().add_method(name, m)
     }
-    pub fn find_var_decl(&self, name: &String) -> Option<VarDecl> {
+    pub fn find_var_decl(&self, name: &String) -> Option<DeclId> {
         self.envs.borrow().find_var_decl(name)
     }
 
-    pub fn get_current_scope_method_decl(&self) -> Option<MethodDecl> {
+    pub fn get_current_scope_method_decl(&self) -> Option<MethodId> {
         self.envs.borrow().get_current_scope_method_decl()
     }
-    pub fn find_method_decl(&self, name: &String) -> Option<MethodDecl> {
+    pub fn find_method_decl(&self, name: &String) -> Option<MethodId> {
         self.envs.borrow().find_method_decl(name)
     }
-    pub fn find_for(&self) -> Option<For> {
+    pub fn find_for(&self) -> Option<StmtId> {
         self.envs.borrow().find_for()
     }
+    pub fn visible_var_names(&self) -> Vec<String> {
+        self.envs.borrow().visible_var_names()
+    }
+    pub fn method_names(&self) -> Vec<String> {
+        self.envs.borrow().method_names()
+    }
 }
 
 pub struct EnvStack {
-    methods: HashMap<String, MethodDecl>,
+    methods: HashMap<String, MethodId>,
     envs: Vec<Env>,
 }
 
@@ -112,14 +131,8 @@ impl EnvStack {
     }
 
     /// Add a new var declation in current env
-    pub fn add_var(&mut self, f: &VarDecl) -> Result<(), EnvError> {
-        let val = f.clone();
-        let res = self
-            .envs
-            .last_mut()
-            .unwrap()
-            .table
-            .insert(f.borrow().name.clone(), val);
+    pub fn add_var(&mut self, name: &str, d: DeclId) -> Result<(), EnvError> {
+        let res = self.envs.last_mut().unwrap().table.insert(name.to_string(), d);
         match res {
             Some(d) => Err(EnvError::DuplicatedVar(d)),
             None => Ok(()),
@@ -127,8 +140,8 @@ impl EnvStack {
     }
 
     /// Add a new method
-    pub fn add_method(&mut self, m: &MethodDecl) -> Result<(), EnvError> {
-        let res = self.methods.insert(m.borrow().name.clone(), m.clone());
+    pub fn add_method(&mut self, name: &str, m: MethodId) -> Result<(), EnvError> {
+        let res = self.methods.insert(name.to_string(), m);
         match res {
             Some(dup_decl) => Err(EnvError::DuplicatedMethod(dup_decl)),
             None => Ok(()),
@@ -136,35 +149,49 @@ impl EnvStack {
     }
 
     /// Find variable declation with given name in current scope
-    pub fn find_var_decl(&self, name: &String) -> Option<VarDecl> {
+    pub fn find_var_decl(&self, name: &String) -> Option<DeclId> {
         for env in self.envs.iter().rev() {
-            match env.table.get(name) {
-                Some(d) => return Some(d.clone()),
-                None => (),
+            if let Some(&d) = env.table.get(name) {
+                return Some(d);
             }
         }
         None
     }
 
     /// Find method declation in current scope
-    pub fn get_current_scope_method_decl(&self) -> Option<MethodDecl> {
+    pub fn get_current_scope_method_decl(&self) -> Option<MethodId> {
         for env in self.envs.iter().rev() {
-            if let EnvType::Method(m) = &env.type_ {
-                return Some(m.clone());
+            if let EnvType::Method(m) = env.type_ {
+                return Some(m);
             }
         }
         None
     }
 
+    /// Names of the variables of every scope, sorted
+    pub fn visible_var_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.envs.iter().flat_map(|e| e.table.keys().cloned()).collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Names of the methods declared so far
+    pub fn method_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.methods.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
     /// Find method declation by method name
-    pub fn find_method_decl(&self, name: &String) -> Option<MethodDecl> {
-        self.methods.get(name).cloned()
+    pub fn find_method_decl(&self, name: &String) -> Option<MethodId> {
+        self.methods.get(name).copied()
     }
     /// Find method declation in current scope
-    pub fn find_for(&self) -> Option<For> {
+    pub fn find_for(&self) -> Option<StmtId> {
         for env in self.envs.iter().rev() {
-            if let EnvType::For(m) = &env.type_ {
-                return Some(m.clone());
+            if let EnvType::For(f) = env.type_ {
+                return Some(f);
             }
         }
         None
@@ -1,57 +1,97 @@
+//! IR built by the semantic analyzer
+//!
+//! Declarations, expressions and statements are stored in per-program
+//! arenas of [`IRRoot`] and refer to each other by typed ids, so the IR
+//! is plain data that can be walked and changed without runtime borrow
+//! checks, and sent to other threads.
+
+use crate::span::Span;
 use crate::token;
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::fmt;
+
+macro_rules! ir_id {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $name(pub usize);
+
+        impl $name {
+            pub fn index(self) -> usize {
+                self.0
+            }
+        }
+    };
+}
 
-#[derive(Debug)]
+ir_id!(
+    /// Variable declaration: field, parameter, local or loop index
+    DeclId
+);
+ir_id!(
+    /// Method declaration
+    MethodId
+);
+ir_id!(ExprId);
+ir_id!(StmtId);
+
+#[derive(Debug, Clone)]
 pub struct Block {
-    pub var_decls: Vec<VarDecl>,
-    pub statements: Vec<Statement>,
+    pub var_decls: Vec<DeclId>,
+    pub statements: Vec<StmtId>,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Location {
-    pub decl: VarDecl,
-    pub arr_size: Option<Expr>,
+    pub decl: DeclId,
+    pub arr_size: Option<ExprId>,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Assign {
     pub dst: Location,
     pub op: AssignOp,
-    pub val: Expr,
+    pub val: ExprId,
+    pub span: Span,
 }
 
-#[derive(Debug)]
-pub struct IfElse0 {
-    pub cond: Expr,
-    pub true_block: Option<Block>,
+#[derive(Debug, Clone)]
+pub struct IfElse {
+    pub cond: ExprId,
+    pub true_block: Block,
     pub false_block: Option<Block>,
+    pub span: Span,
 }
-pub type IfElse = Rc<RefCell<IfElse0>>;
 
-#[derive(Debug)]
-pub struct For0 {
-    pub index_decl: VarDecl,
-    pub start: Expr,
-    pub end: Expr,
-    pub block: Option<Block>,
+#[derive(Debug, Clone)]
+pub struct For {
+    pub index_decl: DeclId,
+    pub start: ExprId,
+    pub end: ExprId,
+    pub block: Block,
+    pub span: Span,
 }
-pub type For = Rc<RefCell<For0>>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Return {
-    pub func: MethodDecl,
-    pub val: Option<Expr>,
+    pub func: MethodId,
+    pub val: Option<ExprId>,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+/// `break` out of the `for` statement `for_`
+#[derive(Debug, Clone)]
 pub struct Break {
-    pub for_: For,
+    pub for_: StmtId,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+/// `continue` with the next iteration of the `for` statement `for_`
+#[derive(Debug, Clone)]
 pub struct Continue {
-    pub for_: For,
+    pub for_: StmtId,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -80,6 +120,8 @@ pub enum Type {
     Int,
     Bool,
     Void,
+    /// type of an expression that failed to check
+    Error,
 }
 
 impl Type {
@@ -92,6 +134,18 @@ impl Type {
     }
 }
 
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Int => "int",
+            Self::Bool => "boolean",
+            Self::Void => "void",
+            Self::Error => "{error}",
+        };
+        write!(f, "{}", s)
+    }
+}
+
 #[derive(Debug, Copy, PartialEq, Eq, Clone)]
 pub enum BinaryOp {
     Or,  // logical or
@@ -157,74 +211,84 @@ impl BinaryOp {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Binary {
-    pub lhs: Expr,
-    pub rhs: Expr,
+    pub lhs: ExprId,
+    pub rhs: ExprId,
     pub op: BinaryOp,
 }
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum UnaryOp {
     NegInt,
     NegBool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Unary {
-    pub expr: Expr,
+    pub expr: ExprId,
     pub op: UnaryOp,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum CalloutArg {
-    Expr(Expr),
-    StringLiteral(StringLiteral),
+    Expr(ExprId),
+    StringLiteral(StringLiteral, Span),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Method {
-    pub decl: MethodDecl,
-    pub args: Vec<Expr>,
+    pub decl: MethodId,
+    pub args: Vec<ExprId>,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Callout {
     pub name: StringLiteral,
     pub args: Vec<CalloutArg>,
+    pub span: Span,
 }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Call {
     Method(Method),
     Callout(Callout),
 }
 
-#[derive(Debug)]
+impl Call {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Method(m) => m.span,
+            Self::Callout(c) => c.span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Literal {
     Int(i32),
     Boolean(bool),
 }
 
-pub type Expr = Rc<RefCell<Expr0>>;
-
-#[derive(Debug)]
-pub struct Expr0 {
+#[derive(Debug, Clone)]
+pub struct Expr {
     pub type_: Type,
     pub expr: ExprType,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ExprType {
     Location(Location),
     Literal(Literal),
     Call(Call),
     Unary(Unary),
     Binary(Binary),
+    /// expression that failed to check
+    Error,
 }
 
-pub type Statement = Rc<RefCell<Statement0>>;
-
-#[derive(Debug)]
-pub enum Statement0 {
+#[derive(Debug, Clone)]
+pub enum Statement {
     Assign(Assign),
     Call(Call),
     IfElse(IfElse),
@@ -235,41 +299,126 @@ pub enum Statement0 {
     Block(Block),
 }
 
-pub type MethodDecl = Rc<RefCell<MethodDecl0>>;
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Assign(s) => s.span,
+            Self::Call(s) => s.span(),
+            Self::IfElse(s) => s.span,
+            Self::For(s) => s.span,
+            Self::Return(s) => s.span,
+            Self::Break(s) => s.span,
+            Self::Continue(s) => s.span,
+            Self::Block(s) => s.span,
+        }
+    }
+}
 
-#[derive(Debug)]
-pub struct MethodDecl0 {
+#[derive(Debug, Clone)]
+pub struct MethodDecl {
     pub return_type: Type,
     pub name: Identifier,
-    pub args: Vec<VarDecl>,
-    pub block: Option<Block>,
+    pub args: Vec<DeclId>,
+    pub block: Block,
+    /// control can reach the end of a method returning a result, which
+    /// must fail at runtime
+    pub needs_fall_off_trap: bool,
+    pub span: Span,
 }
 
-pub type VarDecl = Rc<RefCell<VarDecl0>>;
-#[derive(Debug)]
-pub struct VarDecl0 {
+#[derive(Debug, Clone)]
+pub struct VarDecl {
     pub type_: Type,
     pub name: Identifier,
     pub arr_size: Option<i32>,
+    pub span: Span,
 }
 
-impl VarDecl0 {
+impl VarDecl {
     pub fn is_array(&self) -> bool {
         self.arr_size.is_some()
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
 pub struct ProgramClassDecl {
-    pub field_decls: Vec<VarDecl>,
-    pub method_decls: Vec<MethodDecl>,
+    pub field_decls: Vec<DeclId>,
+    pub method_decls: Vec<MethodId>,
+    pub span: Span,
 }
 
-
-
-#[derive(Debug)]
+/// A program and the arenas of its nodes
+#[derive(Debug, Clone, Default)]
 pub struct IRRoot {
     pub root: ProgramClassDecl,
+    pub decls: Vec<VarDecl>,
+    pub methods: Vec<MethodDecl>,
+    pub exprs: Vec<Expr>,
+    pub stmts: Vec<Statement>,
+}
+
+impl IRRoot {
+    pub fn decl(&self, id: DeclId) -> &VarDecl {
+        &self.decls[id.0]
+    }
+
+    pub fn decl_mut(&mut self, id: DeclId) -> &mut VarDecl {
+        &mut self.decls[id.0]
+    }
+
+    pub fn method(&self, id: MethodId) -> &MethodDecl {
+        &self.methods[id.0]
+    }
+
+    pub fn method_mut(&mut self, id: MethodId) -> &mut MethodDecl {
+        &mut self.methods[id.0]
+    }
+
+    pub fn expr(&self, id: ExprId) -> &Expr {
+        &self.exprs[id.0]
+    }
+
+    pub fn expr_mut(&mut self, id: ExprId) -> &mut Expr {
+        &mut self.exprs[id.0]
+    }
+
+    pub fn stmt(&self, id: StmtId) -> &Statement {
+        &self.stmts[id.0]
+    }
+
+    pub fn stmt_mut(&mut self, id: StmtId) -> &mut Statement {
+        &mut self.stmts[id.0]
+    }
+
+    pub fn add_decl(&mut self, d: VarDecl) -> DeclId {
+        self.decls.push(d);
+        DeclId(self.decls.len() - 1)
+    }
+
+    pub fn add_method(&mut self, m: MethodDecl) -> MethodId {
+        self.methods.push(m);
+        MethodId(self.methods.len() - 1)
+    }
+
+    pub fn add_expr(&mut self, e: Expr) -> ExprId {
+        self.exprs.push(e);
+        ExprId(self.exprs.len() - 1)
+    }
+
+    pub fn add_stmt(&mut self, s: Statement) -> StmtId {
+        self.stmts.push(s);
+        StmtId(self.stmts.len() - 1)
+    }
+
+    /// Fields of the program, in source order
+    pub fn fields(&self) -> impl Iterator<Item = (DeclId, &VarDecl)> {
+        self.root.field_decls.iter().map(|&id| (id, self.decl(id)))
+    }
+
+    /// Methods of the program, in source order
+    pub fn method_decls(&self) -> impl Iterator<Item = (MethodId, &MethodDecl)> {
+        self.root.method_decls.iter().map(|&id| (id, self.method(id)))
+    }
 }
 
 pub type StringLiteral = String;
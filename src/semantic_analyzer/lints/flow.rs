@@ -0,0 +1,181 @@
+//! lints following the control flow inside a method
+
+use super::{walk_block, walk_call, walk_expr, walk_location, Lint, Node, Warning};
+use crate::semantic_analyzer::ir;
+use crate::span::Span;
+use std::collections::HashSet;
+
+/// `s` never passes control to the statement after it
+fn diverges(s: &ir::Statement, ir: &ir::IRRoot) -> bool {
+    match s {
+        ir::Statement::Return(_) | ir::Statement::Break(_) | ir::Statement::Continue(_) => true,
+        ir::Statement::Block(b) => block_diverges(b, ir),
+        ir::Statement::IfElse(i) => {
+            block_diverges(&i.true_block, ir) && i.false_block.as_ref().is_some_and(|b| block_diverges(b, ir))
+        }
+        ir::Statement::Assign(_) | ir::Statement::Call(_) | ir::Statement::For(_) => false,
+    }
+}
+
+fn block_diverges(b: &ir::Block, ir: &ir::IRRoot) -> bool {
+    b.statements.iter().any(|&s| diverges(ir.stmt(s), ir))
+}
+
+fn check_unreachable(b: &ir::Block, ir: &ir::IRRoot, warnings: &mut Vec<Warning>) {
+    for (i, &s) in b.statements.iter().enumerate() {
+        let s = ir.stmt(s);
+        match s {
+            ir::Statement::IfElse(ifelse) => {
+                for b in std::iter::once(&ifelse.true_block).chain(&ifelse.false_block) {
+                    check_unreachable(b, ir, warnings);
+                }
+            }
+            ir::Statement::For(l) => check_unreachable(&l.block, ir, warnings),
+            ir::Statement::Block(b) => check_unreachable(b, ir, warnings),
+            _ => (),
+        }
+        if diverges(s, ir) {
+            if let Some(&next) = b.statements.get(i + 1) {
+                let span = ir.stmt(next).span();
+                warnings.push(Warning::new(Lint::UnreachableCode, String::from("unreachable statement"), span));
+            }
+            return;
+        }
+    }
+}
+
+/// Statements after a `return`, `break` or `continue`
+pub fn unreachable_code(root: &ir::IRRoot) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    for (_, m) in root.method_decls() {
+        check_unreachable(&m.block, root, &mut warnings);
+    }
+    warnings
+}
+
+/// Methods returning a result whose end is reachable
+pub fn missing_returns(root: &ir::IRRoot) -> Vec<Warning> {
+    root.method_decls()
+        .map(|(_, m)| m)
+        .filter(|m| m.needs_fall_off_trap)
+        .map(|m| {
+            let end = m.block.span.end;
+            let message = format!("method `{}` can reach its end without returning a value", m.name);
+            Warning::new(Lint::MissingReturn, message, Span::new(end - 1, end))
+                .with_related(m.span, format!("`{}` declared to return {} here", m.name, m.return_type))
+        })
+        .collect()
+}
+
+/// Definite assignment of the local variables of a method
+struct Assignments<'a> {
+    ir: &'a ir::IRRoot,
+    locals: HashSet<ir::DeclId>,
+    reported: HashSet<ir::DeclId>,
+    warnings: Vec<Warning>,
+}
+
+impl Assignments<'_> {
+    fn read(&mut self, l: &ir::Location, assigned: &HashSet<ir::DeclId>) {
+        if self.locals.contains(&l.decl) && !assigned.contains(&l.decl) && self.reported.insert(l.decl) {
+            let d = self.ir.decl(l.decl);
+            let message = format!("variable `{}` is read before it is assigned", d.name);
+            let w = Warning::new(Lint::UninitializedRead, message, l.span)
+                .with_related(d.span, format!("`{}` declared here", d.name));
+            self.warnings.push(w);
+        }
+    }
+
+    fn expr(&mut self, e: ir::ExprId, assigned: &HashSet<ir::DeclId>) {
+        walk_expr(self.ir, e, &mut |n| {
+            if let Node::Read(l) = n {
+                self.read(l, assigned);
+            }
+        });
+    }
+
+    /// Check the reads of `b`, adding the variables it assigns to
+    /// `assigned`; returns whether `b` diverges
+    fn block(&mut self, b: &ir::Block, assigned: &mut HashSet<ir::DeclId>) -> bool {
+        let ir = self.ir;
+        for &s in &b.statements {
+            match ir.stmt(s) {
+                ir::Statement::Assign(a) => {
+                    walk_location(ir, &a.dst, &mut |n| {
+                        if let Node::Read(l) = n {
+                            self.read(l, assigned);
+                        }
+                    });
+                    if a.op != ir::AssignOp::Assign {
+                        self.read(&a.dst, assigned);
+                    }
+                    self.expr(a.val, assigned);
+                    assigned.insert(a.dst.decl);
+                }
+                ir::Statement::Call(c) => walk_call(ir, c, &mut |n| {
+                    if let Node::Read(l) = n {
+                        self.read(l, assigned);
+                    }
+                }),
+                ir::Statement::IfElse(i) => {
+                    self.expr(i.cond, assigned);
+                    let mut true_assigned = assigned.clone();
+                    let true_diverges = self.block(&i.true_block, &mut true_assigned);
+                    let mut false_assigned = assigned.clone();
+                    let false_diverges = match &i.false_block {
+                        Some(b) => self.block(b, &mut false_assigned),
+                        None => false,
+                    };
+                    // a branch that diverges does not reach the next statement
+                    *assigned = match (true_diverges, false_diverges) {
+                        (true, false) => false_assigned,
+                        (false, true) => true_assigned,
+                        _ => true_assigned.intersection(&false_assigned).copied().collect(),
+                    };
+                    if true_diverges && false_diverges {
+                        return true;
+                    }
+                }
+                ir::Statement::For(l) => {
+                    self.expr(l.start, assigned);
+                    self.expr(l.end, assigned);
+                    // the body may not run at all
+                    self.block(&l.block, &mut assigned.clone());
+                }
+                ir::Statement::Return(r) => {
+                    r.val.iter().for_each(|&e| self.expr(e, assigned));
+                    return true;
+                }
+                ir::Statement::Break(_) | ir::Statement::Continue(_) => return true,
+                ir::Statement::Block(b) => {
+                    if self.block(b, assigned) {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+}
+
+/// Local variables read before they are definitely assigned
+pub fn uninitialized_reads(root: &ir::IRRoot) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    for (_, m) in root.method_decls() {
+        let mut locals = HashSet::new();
+        walk_block(root, &m.block, &mut |n| {
+            if let Node::Decl(d) = n {
+                locals.insert(d);
+            }
+        });
+        let mut a = Assignments {
+            ir: root,
+            locals,
+            reported: HashSet::new(),
+            warnings: Vec::new(),
+        };
+        a.block(&m.block, &mut HashSet::new());
+        warnings.extend(a.warnings);
+    }
+    warnings
+}
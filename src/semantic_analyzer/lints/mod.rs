@@ -0,0 +1,310 @@
+//! warnings about legal but suspicious programs, checked on the IR
+
+mod flow;
+mod shadow;
+mod unused;
+
+use super::ir;
+use super::symbols::SymbolKind;
+use crate::span::Span;
+use crate::visit::ir::{self as visit, Visitor};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    UnusedVariable,
+    UnusedParameter,
+    UnreachableCode,
+    UninitializedRead,
+    ShadowedGlobal,
+    UnusedMethod,
+    MissingReturn,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 7] = [
+        Self::UnusedVariable,
+        Self::UnusedParameter,
+        Self::UnreachableCode,
+        Self::UninitializedRead,
+        Self::ShadowedGlobal,
+        Self::UnusedMethod,
+        Self::MissingReturn,
+    ];
+
+    /// Name used by the `-W`, `-A` and `-D` options
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::UnusedVariable => "unused-variable",
+            Self::UnusedParameter => "unused-parameter",
+            Self::UnreachableCode => "unreachable-code",
+            Self::UninitializedRead => "uninitialized-read",
+            Self::ShadowedGlobal => "shadowed-global",
+            Self::UnusedMethod => "unused-method",
+            Self::MissingReturn => "missing-return",
+        }
+    }
+}
+
+impl FromStr for Lint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|l| l.name() == s)
+            .ok_or_else(|| format!("unknown lint: {}", s))
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// What to do when a lint fires
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// `-A`: ignore it
+    Allow,
+    /// `-W`: report a warning
+    Warn,
+    /// `-D`: report an error
+    Deny,
+}
+
+/// Level of every lint, `Warn` unless set otherwise
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LintLevels {
+    levels: HashMap<Lint, Level>,
+}
+
+impl LintLevels {
+    pub fn get(&self, lint: Lint) -> Level {
+        self.levels.get(&lint).copied().unwrap_or(Level::Warn)
+    }
+
+    pub fn set(&mut self, lint: Lint, level: Level) {
+        self.levels.insert(lint, level);
+    }
+
+    pub fn set_all(&mut self, level: Level) {
+        for lint in Lint::ALL {
+            self.set(lint, level);
+        }
+    }
+}
+
+/// A lint firing at a construct
+#[derive(Debug)]
+pub struct Warning {
+    pub lint: Lint,
+    pub message: String,
+    pub span: Span,
+    /// related declaration, with a description of it
+    pub related: Option<(Span, String)>,
+}
+
+impl Warning {
+    fn new(lint: Lint, message: String, span: Span) -> Self {
+        Self {
+            lint,
+            message,
+            span,
+            related: None,
+        }
+    }
+
+    fn with_related(mut self, span: Span, message: String) -> Self {
+        self.related = Some((span, message));
+        self
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Run every lint that is not allowed, in source order
+pub fn run_lints(root: &ir::IRRoot, levels: &LintLevels) -> Vec<Warning> {
+    let lints: [fn(&ir::IRRoot) -> Vec<Warning>; 7] = [
+        unused::unused_variables,
+        unused::unused_parameters,
+        unused::unused_methods,
+        flow::unreachable_code,
+        flow::uninitialized_reads,
+        flow::missing_returns,
+        shadow::shadowed_globals,
+    ];
+    let mut warnings: Vec<Warning> = lints
+        .iter()
+        .flat_map(|lint| lint(root))
+        .filter(|w| levels.get(w.lint) != Level::Allow)
+        .collect();
+    warnings.sort_by_key(|w| w.span.start);
+    warnings
+}
+
+/// Declaration or use of a symbol found by [`walk_block`]
+enum Node<'a> {
+    Decl(ir::DeclId),
+    IndexDecl(ir::DeclId),
+    Read(&'a ir::Location),
+    Write(&'a ir::Location),
+    Call(&'a ir::Method),
+}
+
+/// Visitor passing the declarations and uses of a block to `f`
+struct Nodes<'f, 'a> {
+    f: &'f mut dyn FnMut(Node<'a>),
+}
+
+impl<'a> Visitor<'a> for Nodes<'_, 'a> {
+    fn visit_var_decl(&mut self, _ir: &'a ir::IRRoot, id: ir::DeclId, kind: SymbolKind) {
+        match kind {
+            SymbolKind::LoopIndex => (self.f)(Node::IndexDecl(id)),
+            _ => (self.f)(Node::Decl(id)),
+        }
+    }
+
+    fn visit_statement(&mut self, ir: &'a ir::IRRoot, id: ir::StmtId) {
+        match ir.stmt(id) {
+            ir::Statement::Assign(a) => {
+                visit::walk_location(self, ir, &a.dst);
+                // a compound assignment reads its destination as well
+                if a.op != ir::AssignOp::Assign {
+                    (self.f)(Node::Read(&a.dst));
+                }
+                self.visit_expr(ir, a.val);
+                (self.f)(Node::Write(&a.dst));
+            }
+            _ => visit::walk_statement(self, ir, id),
+        }
+    }
+
+    fn visit_location(&mut self, ir: &'a ir::IRRoot, l: &'a ir::Location) {
+        (self.f)(Node::Read(l));
+        visit::walk_location(self, ir, l);
+    }
+
+    fn visit_call(&mut self, ir: &'a ir::IRRoot, c: &'a ir::Call) {
+        if let ir::Call::Method(m) = c {
+            (self.f)(Node::Call(m));
+        }
+        visit::walk_call(self, ir, c);
+    }
+}
+
+fn walk_expr<'a>(ir: &'a ir::IRRoot, e: ir::ExprId, f: &mut dyn FnMut(Node<'a>)) {
+    Nodes { f }.visit_expr(ir, e);
+}
+
+fn walk_location<'a>(ir: &'a ir::IRRoot, l: &'a ir::Location, f: &mut dyn FnMut(Node<'a>)) {
+    visit::walk_location(&mut Nodes { f }, ir, l);
+}
+
+fn walk_call<'a>(ir: &'a ir::IRRoot, c: &'a ir::Call, f: &mut dyn FnMut(Node<'a>)) {
+    Nodes { f }.visit_call(ir, c);
+}
+
+/// Call `f` on every declaration and use of a symbol in `b`, in source order
+fn walk_block<'a>(ir: &'a ir::IRRoot, b: &'a ir::Block, f: &mut dyn FnMut(Node<'a>)) {
+    Nodes { f }.visit_block(ir, b);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::check;
+
+    /// lints firing on `src`, with the source text they point at
+    fn lint(src: &str) -> Vec<(Lint, &str)> {
+        let ir = check(src);
+        run_lints(&ir, &LintLevels::default())
+            .into_iter()
+            .map(|w| (w.lint, &src[w.span.start..w.span.end]))
+            .collect()
+    }
+
+    #[test]
+    fn test_unused() {
+        let src = "class Program { int g, h; int f(int a, int b) { return a; } void g2() {} \
+                   void main() { int x, y; x = f(h, 0); } }";
+        assert_eq!(
+            lint(src),
+            vec![
+                (Lint::UnusedVariable, "g"),
+                (Lint::UnusedParameter, "b"),
+                (Lint::UnusedMethod, "void g2() {}"),
+                (Lint::UnusedVariable, "x"),
+                (Lint::UnusedVariable, "y"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unreachable_code() {
+        let src = "class Program { int f() { if (true) { return 1; } else { return 2; } return 3; } \
+                   void main() { for i = 0, 1 { continue; f(); } { return; } f(); } }";
+        assert_eq!(
+            lint(src),
+            vec![
+                (Lint::UnreachableCode, "return 3;"),
+                (Lint::UnreachableCode, "f()"),
+                (Lint::UnreachableCode, "f()"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_uninitialized_read() {
+        let src = "class Program { void main() { int x, y, z; boolean c; c = true; \
+                   if (c) { x = 1; y = 1; } else { x = 2; } \
+                   for i = 0, 10 { z = 1; } \
+                   callout(\"printf\", \"%d %d %d\", x, y, z); } }";
+        assert_eq!(lint(src), vec![(Lint::UninitializedRead, "y"), (Lint::UninitializedRead, "z")]);
+    }
+
+    #[test]
+    fn test_missing_return() {
+        let src = "class Program { int f() { if (true) { return 1; } } \
+                   int g() { { if (true) { return 1; } else { return 2; } } } \
+                   int h() { for i = 0, 1 { return 1; } } \
+                   void main() { f(); g(); h(); } }";
+        assert_eq!(lint(src), vec![(Lint::MissingReturn, "}"), (Lint::MissingReturn, "}")]);
+
+        let ir = check(src);
+        let traps: Vec<bool> = ir.method_decls().map(|(_, m)| m.needs_fall_off_trap).collect();
+        assert_eq!(traps, vec![true, false, true, false]);
+    }
+
+    #[test]
+    fn test_shadowed_global() {
+        let src = "class Program { int a; void main() { int a; a = 0; for a = 0, a { } callout(\"f\", a); } }";
+        assert_eq!(
+            lint(src),
+            vec![
+                (Lint::UnusedVariable, "a"),
+                (Lint::ShadowedGlobal, "a"),
+                (Lint::ShadowedGlobal, "a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_levels() {
+        let mut levels = LintLevels::default();
+        assert_eq!(levels.get(Lint::UnusedMethod), Level::Warn);
+        levels.set_all(Level::Allow);
+        levels.set(Lint::UnusedMethod, Level::Deny);
+        assert_eq!(levels.get(Lint::UnusedVariable), Level::Allow);
+        assert_eq!(levels.get(Lint::UnusedMethod), Level::Deny);
+        assert_eq!("unused-method".parse(), Ok(Lint::UnusedMethod));
+        assert!("unused".parse::<Lint>().is_err());
+    }
+}
@@ -0,0 +1,26 @@
+//! local names hiding global ones
+
+use super::{walk_block, Lint, Node, Warning};
+use crate::semantic_analyzer::ir;
+
+/// Parameters, local variables and loop indexes with the name of a field
+pub fn shadowed_globals(root: &ir::IRRoot) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let mut check = |d: ir::DeclId| {
+        let d = root.decl(d);
+        if let Some((_, f)) = root.fields().find(|(_, f)| f.name == d.name) {
+            let message = format!("`{}` shadows a field", d.name);
+            let w = Warning::new(Lint::ShadowedGlobal, message, d.span)
+                .with_related(f.span, format!("field `{}` declared here", d.name));
+            warnings.push(w);
+        }
+    };
+    for (_, m) in root.method_decls() {
+        m.args.iter().for_each(|&a| check(a));
+        walk_block(root, &m.block, &mut |n| match n {
+            Node::Decl(d) | Node::IndexDecl(d) => check(d),
+            _ => (),
+        });
+    }
+    warnings
+}
@@ -0,0 +1,93 @@
+//! declarations that are never used
+
+use super::{walk_block, Lint, Node, Warning};
+use crate::semantic_analyzer::ir;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Default)]
+struct Uses {
+    read: bool,
+    written: bool,
+}
+
+/// Reads and writes of every variable in the program, by declaration
+fn var_uses(root: &ir::IRRoot) -> HashMap<ir::DeclId, Uses> {
+    let mut uses: HashMap<ir::DeclId, Uses> = HashMap::new();
+    for (_, m) in root.method_decls() {
+        walk_block(root, &m.block, &mut |n| match n {
+            Node::Read(l) => uses.entry(l.decl).or_default().read = true,
+            Node::Write(l) => uses.entry(l.decl).or_default().written = true,
+            _ => (),
+        });
+    }
+    uses
+}
+
+/// Warning for a variable that is never read
+fn unread(lint: Lint, kind: &str, root: &ir::IRRoot, d: ir::DeclId, uses: &HashMap<ir::DeclId, Uses>) -> Option<Warning> {
+    let decl = root.decl(d);
+    let message = match uses.get(&d) {
+        Some(Uses { read: true, .. }) => return None,
+        Some(Uses { written: true, .. }) => format!("{} `{}` is assigned but never read", kind, decl.name),
+        _ => format!("unused {} `{}`", kind, decl.name),
+    };
+    Some(Warning::new(lint, message, decl.span))
+}
+
+/// Fields and local variables that are never read
+pub fn unused_variables(root: &ir::IRRoot) -> Vec<Warning> {
+    let uses = var_uses(root);
+    let mut warnings: Vec<Warning> = root
+        .fields()
+        .filter_map(|(d, _)| unread(Lint::UnusedVariable, "field", root, d, &uses))
+        .collect();
+    for (_, m) in root.method_decls() {
+        walk_block(root, &m.block, &mut |n| {
+            if let Node::Decl(d) = n {
+                warnings.extend(unread(Lint::UnusedVariable, "variable", root, d, &uses));
+            }
+        });
+    }
+    warnings
+}
+
+/// Method parameters that are never read
+pub fn unused_parameters(root: &ir::IRRoot) -> Vec<Warning> {
+    let uses = var_uses(root);
+    root.method_decls()
+        .flat_map(|(_, m)| m.args.iter().copied())
+        .filter_map(|a| unread(Lint::UnusedParameter, "parameter", root, a, &uses))
+        .collect()
+}
+
+/// Methods that cannot be reached by calls starting from `main`
+pub fn unused_methods(root: &ir::IRRoot) -> Vec<Warning> {
+    let callees = |m: ir::MethodId| {
+        let mut res: Vec<ir::MethodId> = Vec::new();
+        walk_block(root, &root.method(m).block, &mut |n| {
+            if let Node::Call(c) = n {
+                res.push(c.decl);
+            }
+        });
+        res
+    };
+
+    let mut reached: HashSet<ir::MethodId> = HashSet::new();
+    let mut todo: Vec<ir::MethodId> = root
+        .method_decls()
+        .filter(|(_, m)| m.name == "main")
+        .map(|(id, _)| id)
+        .collect();
+    while let Some(m) = todo.pop() {
+        if reached.insert(m) {
+            todo.extend(callees(m));
+        }
+    }
+    root.method_decls()
+        .filter(|(id, _)| !reached.contains(id))
+        .map(|(_, m)| {
+            let message = format!("method `{}` is never called from `main`", m.name);
+            Warning::new(Lint::UnusedMethod, message, m.span)
+        })
+        .collect()
+}
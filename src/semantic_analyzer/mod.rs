@@ -1,318 +1,503 @@
 mod env;
-mod ir;
+pub mod ir;
+pub mod lints;
 mod passes;
+pub mod printer;
+mod suggest;
+pub mod symbols;
 
+use crate::span::Span;
 use crate::token;
-use env::{EnvContext, EnvStack, EnvType};
+use env::{EnvContext, EnvError, EnvStack, EnvType};
+pub use passes::{Pass, PassError, PassManager, PassTiming, SemanticCheckError, Stage, Suggestion};
 use passes::*;
 use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
-
-macro_rules! unwrap_or_early_return {
-    ($t:expr) => {
-        match $t {
-            Ok(e) => e,
-            Err(e) => return Err(e),
-        }
-    };
-}
-macro_rules! check_type_or_early_return {
-    ($e:expr, $expected_type:path) => {
-        match $e.borrow().type_ {
-            $expected_type => (),
-            _ => {
-                return Err(vec![SemanticCheckError::TypeMismatch(String::from(
-                    "Early return",
-                ))])
-            }
-        }
-    };
-}
-macro_rules! return_error {
-    ($err:ident) => {
-        return Err(vec![SemanticCheckError::$err]);
-    };
-}
-
+use suggest::similar_name;
+use symbols::{ScopeKind, SymbolKind, SymbolTable, SymbolTableBuilder};
+
+/// Builds the IR and checks the semantic rules
+///
+/// Analysis goes on after an error: a sub-expression that fails to check
+/// gets the poison type `ir::Type::Error`, which satisfies every later
+/// check, so each root cause is reported once.
+///
+/// The checks that are not part of building the IR are run by a
+/// [`PassManager`], to which users can add their own passes. An analyzer
+/// can check any number of programs.
 pub struct SemanticAnalyzer {
     envs: Rc<RefCell<EnvStack>>,
+    /// every method declared in the program with its span, to tell calls
+    /// of later methods from calls of unknown ones
+    method_names: RefCell<HashMap<String, Span>>,
+    errors: RefCell<Vec<SemanticCheckError>>,
+    passes: PassManager,
+    timings: RefCell<Vec<PassTiming>>,
+    symbols: RefCell<SymbolTableBuilder>,
+    /// IR being built
+    ir: RefCell<ir::IRRoot>,
+    /// methods with a malformed signature, which may have lost parameters:
+    /// unknown names in their body and their argument counts are not
+    /// reported
+    recovered: RefCell<HashSet<ir::MethodId>>,
 }
 
-fn create_rc<T>(x: T) -> Rc<RefCell<T>> {
-    Rc::new(RefCell::new(x))
+/// Scope of the symbol table RAII
+struct ScopeGuard<'a> {
+    symbols: &'a RefCell<SymbolTableBuilder>,
 }
 
-fn get_ir_expr_type(e: &ir::ExprType) -> Result<ir::Type, SemanticCheckError> {
+impl Drop for ScopeGuard<'_> {
+    fn drop(&mut self) {
+        self.symbols.borrow_mut().exit();
+    }
+}
+
+fn get_ir_expr_type(e: &ir::ExprType, span: Span, ir: &ir::IRRoot) -> Result<ir::Type, SemanticCheckError> {
     match e {
-        ir::ExprType::Location(e) => Ok(e.decl.borrow().type_),
+        ir::ExprType::Location(e) => Ok(ir.decl(e.decl).type_),
         ir::ExprType::Literal(e) => match e {
             ir::Literal::Int(_) => Ok(ir::Type::Int),
             ir::Literal::Boolean(_) => Ok(ir::Type::Bool),
         },
         ir::ExprType::Call(e) => match e {
-            ir::Call::Method(m) => match m.decl.borrow().return_type {
-                ir::Type::Void => Err(SemanticCheckError::ExprCallNoReturn),
-                t => Ok(t),
-            },
+            ir::Call::Method(m) => {
+                let decl = ir.method(m.decl);
+                match decl.return_type {
+                    ir::Type::Void => Err(SemanticCheckError::ExprCallNoReturn {
+                        method: decl.name.clone(),
+                        span,
+                        decl: decl.span,
+                    }),
+                    t => Ok(t),
+                }
+            }
             ir::Call::Callout(_) => Ok(ir::Type::Int),
         },
-        ir::ExprType::Unary(e) => Ok(e.expr.borrow().type_),
+        ir::ExprType::Unary(e) => match e.op {
+            ir::UnaryOp::NegInt => Ok(ir::Type::Int),
+            ir::UnaryOp::NegBool => Ok(ir::Type::Bool),
+        },
         ir::ExprType::Binary(e) => Ok(e.op.get_return_type()),
+        ir::ExprType::Error => Ok(ir::Type::Error),
+    }
+}
+
+impl Default for SemanticAnalyzer {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl SemanticAnalyzer {
+    /// Analyzer running the default passes
     pub fn new() -> Self {
+        Self::build(PassManager::default())
+    }
+
+    /// Analyzer running `passes`, ordered by their dependencies
+    pub fn with_passes(mut passes: PassManager) -> Result<Self, PassError> {
+        passes.sort()?;
+        Ok(Self::build(passes))
+    }
+
+    fn build(passes: PassManager) -> Self {
         Self {
-            envs: create_rc(EnvStack::new()),
+            envs: Rc::new(RefCell::new(EnvStack::new())),
+            method_names: RefCell::new(HashMap::new()),
+            errors: RefCell::new(Vec::new()),
+            passes,
+            timings: RefCell::new(Vec::new()),
+            recovered: RefCell::new(HashSet::new()),
+            symbols: RefCell::new(SymbolTableBuilder::default()),
+            ir: RefCell::new(ir::IRRoot::default()),
         }
     }
+
     pub fn create_ir(&self, p: token::Program) -> Result<ir::IRRoot, Vec<SemanticCheckError>> {
-        if let Err(errors) = self.pre_ir_check(&p) {
-            return Err(errors);
+        *self.envs.borrow_mut() = EnvStack::new();
+        self.method_names.borrow_mut().clear();
+        self.timings.borrow_mut().clear();
+        *self.symbols.borrow_mut() = SymbolTableBuilder::default();
+
+        let (errors, timings) = self.passes.run_tokens(&p);
+        *self.errors.borrow_mut() = errors;
+        self.timings.borrow_mut().extend(timings);
+        let root = self.construct_ir(p);
+        // IR-level passes can rely on a well-typed IR
+        if self.errors.borrow().is_empty() {
+            let (errors, timings) = self.passes.run_ir(&root);
+            self.errors.borrow_mut().extend(errors);
+            self.timings.borrow_mut().extend(timings);
+        }
+        let errors = self.errors.take();
+        if errors.is_empty() {
+            Ok(root)
+        } else {
+            Err(errors)
         }
-        self.construct_ir(p)
     }
 
-    fn get_ir_field_decls(
-        &self,
-        decls: Vec<token::FieldDecl>,
-    ) -> Result<Vec<ir::VarDecl>, Vec<SemanticCheckError>> {
+    /// Time taken by each pass during the last `create_ir`
+    pub fn timings(&self) -> Vec<PassTiming> {
+        self.timings.borrow().clone()
+    }
+
+    /// Declarations and uses of the program of the last `create_ir`,
+    /// including the parts of an illegal program that could be resolved
+    pub fn symbol_table(&self) -> SymbolTable {
+        self.symbols.borrow().table().clone()
+    }
+
+    fn enter_scope(&self, kind: ScopeKind, span: Span) -> ScopeGuard<'_> {
+        self.symbols.borrow_mut().enter(kind, span);
+        ScopeGuard { symbols: &self.symbols }
+    }
+
+    fn error(&self, e: SemanticCheckError) {
+        self.errors.borrow_mut().push(e);
+    }
+
+    fn decl(&self, id: ir::DeclId) -> ir::VarDecl {
+        self.ir.borrow().decl(id).clone()
+    }
+
+    /// type and span of an expression
+    fn expr_info(&self, id: ir::ExprId) -> (ir::Type, Span) {
+        let ir = self.ir.borrow();
+        let e = ir.expr(id);
+        (e.type_, e.span)
+    }
+
+    fn add_expr(&self, type_: ir::Type, expr: ir::ExprType, span: Span) -> ir::ExprId {
+        self.ir.borrow_mut().add_expr(ir::Expr { type_, expr, span })
+    }
+
+    /// expression standing for a sub-expression that failed to check
+    fn error_expr(&self, span: Span) -> ir::ExprId {
+        self.add_expr(ir::Type::Error, ir::ExprType::Error, span)
+    }
+
+    fn add_stmt(&self, s: ir::Statement) -> ir::StmtId {
+        self.ir.borrow_mut().add_stmt(s)
+    }
+
+    /// report `err` with the found type and span if `e` has neither the
+    /// `expected` type nor the poison type
+    fn check_type<F>(&self, e: ir::ExprId, expected: ir::Type, err: F)
+    where
+        F: FnOnce(ir::Type, Span) -> SemanticCheckError,
+    {
+        let (type_, span) = self.expr_info(e);
+        if type_ != expected && type_ != ir::Type::Error {
+            self.error(err(type_, span));
+        }
+    }
+
+    /// add `d` to the current scope, reporting a duplicate declaration
+    fn add_var(&self, env_ctx: &EnvContext, d: ir::DeclId) -> Result<(), EnvError> {
+        let name = self.decl(d).name;
+        env_ctx.add_var(&name, d)
+    }
+
+    fn duplicated(&self, d: ir::DeclId, e: EnvError) -> SemanticCheckError {
+        let d = self.decl(d);
+        SemanticCheckError::DuplicatedSymbol {
+            name: d.name,
+            span: d.span,
+            prev: e.prev_span(&self.ir.borrow()),
+        }
+    }
+
+    fn get_ir_field_decls(&self, decls: Vec<token::FieldDecl>) -> Vec<ir::DeclId> {
         let env_ctx = EnvContext::new(self.envs.clone(), EnvType::NoEnv);
-        let mut res: Vec<ir::VarDecl> = Vec::new();
-        let mut errors = Vec::new();
+        let mut res: Vec<ir::DeclId> = Vec::new();
         for field_decls in decls {
             for field_decl in field_decls.loc {
                 let t = ir::Type::from(&field_decls.type_);
-                let name = field_decl.name;
-                let arr_size = field_decl.arr_size;
-                let d = ir::VarDecl0 {
+                let name = field_decl.name.name;
+                // out of range sizes are reported by `is_array_size_positive`
+                let arr_size = field_decl
+                    .arr_size
+                    .map(|s| int_literal_value(&s, false).unwrap_or(0));
+                let d = ir::VarDecl {
                     type_: t,
                     name,
                     arr_size,
+                    span: field_decl.span,
                 };
-                let d = create_rc(d);
-                if env_ctx.add_var(&d).is_err() {
-                    let e = SemanticCheckError::DuplicatedSymbol(d.borrow().name.clone());
-                    errors.push(e);
+                let d = self.ir.borrow_mut().add_decl(d);
+                self.symbols.borrow_mut().declare_var(d, &self.decl(d), SymbolKind::Field, field_decl.name.span);
+                if let Err(e) = self.add_var(&env_ctx, d) {
+                    self.error(self.duplicated(d, e));
                     continue;
                 }
                 res.push(d);
             }
         }
-        if !errors.is_empty() {
-            Err(errors)
-        } else {
-            Ok(res)
-        }
+        res
     }
 
-    fn get_ir_method_arg(&self, t: &token::MethodArg) -> ir::VarDecl {
-        create_rc(ir::VarDecl0 {
+    fn get_ir_method_arg(&self, t: &token::MethodArg) -> ir::DeclId {
+        self.ir.borrow_mut().add_decl(ir::VarDecl {
             type_: ir::Type::from(&t.type_),
-            name: t.name.clone(),
+            name: t.name.name.clone(),
             arr_size: None,
+            span: t.name.span,
         })
     }
-    fn get_ir_var_decls(
-        &self,
-        t: Vec<token::VarDecl>,
-    ) -> Result<Vec<ir::VarDecl>, Vec<SemanticCheckError>> {
+    fn get_ir_var_decls(&self, t: Vec<token::VarDecl>) -> Vec<ir::DeclId> {
         let env_ctx = EnvContext::new(self.envs.clone(), EnvType::NoEnv);
-        let mut res: Vec<ir::VarDecl> = Vec::new();
-        let mut errors = Vec::new();
+        let mut res: Vec<ir::DeclId> = Vec::new();
         for decls in t {
             for name in decls.identifiers {
                 let t = ir::Type::from(&decls.type_);
-                let d = ir::VarDecl0 {
+                let d = ir::VarDecl {
                     type_: t,
-                    name,
+                    name: name.name,
                     arr_size: None,
+                    span: name.span,
                 };
-                let d = create_rc(d);
-                if env_ctx.add_var(&d).is_err() {
-                    let e = SemanticCheckError::DuplicatedSymbol(d.borrow().name.clone());
-                    errors.push(e);
+                let d = self.ir.borrow_mut().add_decl(d);
+                self.symbols.borrow_mut().declare_var(d, &self.decl(d), SymbolKind::Local, name.span);
+                if let Err(EnvError::DuplicatedVar(prev)) = self.add_var(&env_ctx, d) {
+                    let ir::VarDecl { name, span, .. } = self.decl(d);
+                    let is_for_index = match env_ctx.find_for() {
+                        Some(f) => matches!(self.ir.borrow().stmt(f), ir::Statement::For(f) if f.index_decl == prev),
+                        None => false,
+                    };
+                    let prev = self.decl(prev).span;
+                    let e = if is_for_index {
+                        SemanticCheckError::ForIndexRedeclared { name, span, index: prev }
+                    } else {
+                        SemanticCheckError::DuplicatedSymbol { name, span, prev }
+                    };
+                    self.error(e);
                     continue;
                 }
                 res.push(d);
             }
         }
-        if !errors.is_empty() {
-            Err(errors)
-        } else {
-            Ok(res)
-        }
+        res
     }
 
-    fn get_ir_location(&self, t: token::Location) -> Result<ir::Location, Vec<SemanticCheckError>> {
-        let mut errors = Vec::new();
+    /// `None` if the location does not name a variable
+    fn get_ir_location(&self, t: token::Location) -> Option<ir::Location> {
         let env_ctx = EnvContext::new(self.envs.clone(), EnvType::NoEnv);
-        let var_decl = env_ctx.find_var_decl(&t.name);
-        if var_decl.is_none() {
-            errors.push(SemanticCheckError::UnknownSymbol(t.name));
-            return Err(errors);
-        }
-        let var_decl = var_decl.unwrap();
-        let offset = match t.arr_size {
-            Some(i) => match self.get_ir_expr(i) {
-                Ok(i) => {
-                    let type_ = i.borrow().type_;
-                    let arr_size = &var_decl.borrow().arr_size;
-                    if type_ == ir::Type::Int && arr_size.is_some() {
-                        Some(i)
-                    } else {
-                        if arr_size.is_some() {
-                            errors.push(SemanticCheckError::ArrayLocationOnNonArrayVar);
-                        }
-                        if type_ != ir::Type::Int {
-                            errors.push(SemanticCheckError::ArrayLocationOffsetTypeError);
-                        }
-                        None
+        let var_decl = match env_ctx.find_var_decl(&t.name.name) {
+            Some(d) => d,
+            None => {
+                let (name, span) = (t.name.name, t.name.span);
+                let e = match env_ctx.find_method_decl(&name) {
+                    Some(m) => SemanticCheckError::LocationNotVariable {
+                        name,
+                        span,
+                        decl: self.ir.borrow().method(m).span,
+                    },
+                    None => {
+                        let suggestion = similar_name(&name, &env_ctx.visible_var_names())
+                            .map(Suggestion::Similar)
+                            .or_else(|| similar_name(&name, &env_ctx.method_names()).map(Suggestion::Method));
+                        SemanticCheckError::UnknownSymbol { name, span, suggestion }
                     }
+                };
+                let method = env_ctx.get_current_scope_method_decl();
+                let recovered = method.is_some_and(|m| self.recovered.borrow().contains(&m));
+                if !(recovered && matches!(e, SemanticCheckError::UnknownSymbol { .. })) {
+                    self.error(e);
                 }
-                Err(e) => {
-                    errors.extend(e);
-                    None
+                // the index is still checked on its own
+                if let Some(i) = t.arr_size {
+                    self.get_ir_expr(i);
+                }
+                return None;
+            }
+        };
+        self.symbols.borrow_mut().use_var(var_decl, t.name.span);
+        let d = self.decl(var_decl);
+        let is_array = d.is_array();
+        let name = t.name.name;
+        let decl = d.span;
+        let offset = match t.arr_size {
+            Some(i) => {
+                let i = self.get_ir_expr(i);
+                if !is_array {
+                    self.error(SemanticCheckError::ArrayLocationOnNonArrayVar {
+                        name,
+                        span: t.span,
+                        decl,
+                    });
+                }
+                self.check_type(i, ir::Type::Int, |found, span| {
+                    SemanticCheckError::ArrayLocationOffsetTypeError { found, span }
+                });
+                Some(i)
+            }
+            None => {
+                if is_array {
+                    self.error(SemanticCheckError::ArrayLocationWithoutOffset {
+                        name,
+                        span: t.span,
+                        decl,
+                    });
                 }
-            },
-            None if !var_decl.borrow().is_array() => None,
-            _ => {
-                errors.push(SemanticCheckError::TypeMismatch(String::from(
-                    "location does not have offset, but delc is an array",
-                )));
                 None
             }
         };
-        if errors.is_empty() {
-            Ok(ir::Location {
-                decl: var_decl,
-                arr_size: offset,
-            })
-        } else {
-            Err(errors)
-        }
+        Some(ir::Location {
+            decl: var_decl,
+            arr_size: offset,
+            span: t.span,
+        })
     }
 
-    fn get_ir_method(&self, t: token::Method) -> IRResult<ir::Method> {
+    /// `None` if the method is not declared
+    fn get_ir_method(&self, t: token::Method) -> Option<ir::Method> {
         let env_ctx = EnvContext::new(self.envs.clone(), EnvType::NoEnv);
-        let method_decl = match env_ctx.find_method_decl(&t.name) {
+        let args: Vec<ir::ExprId> = t.args.into_iter().map(|a| self.get_ir_expr(a)).collect();
+        let method_decl = match env_ctx.find_method_decl(&t.name.name) {
             Some(m) => m,
-            None => return Err(vec![SemanticCheckError::UnknownSymbol(t.name)]),
+            None => {
+                let (name, span) = (t.name.name, t.name.span);
+                let decl = self.method_names.borrow().get(&name).copied();
+                let e = match decl {
+                    Some(decl) => SemanticCheckError::MethodCalledBeforeDeclaration { name, span, decl },
+                    None => {
+                        // a name unlike any method is likely an external function
+                        let suggestion = match similar_name(&name, &env_ctx.method_names()) {
+                            Some(s) => Suggestion::Similar(s),
+                            None => Suggestion::Callout,
+                        };
+                        SemanticCheckError::UnknownSymbol {
+                            name,
+                            span,
+                            suggestion: Some(suggestion),
+                        }
+                    }
+                };
+                self.error(e);
+                return None;
+            }
         };
 
-        let mut args = Vec::with_capacity(t.args.len());
-        for arg in t.args {
-            let expr = unwrap_or_early_return!(self.get_ir_expr(arg));
-            args.push(expr);
-        }
-
-        if args.len() != method_decl.borrow().args.len() {
-            return_error!(MethodArgumentNotMatch);
-        }
-        for (arg, arg_decl) in args.iter().zip(method_decl.borrow().args.iter()) {
-            if arg.borrow().type_ != arg_decl.borrow().type_ {
-                return_error!(MethodArgumentNotMatch);
+        self.symbols.borrow_mut().use_method(method_decl, t.name.span);
+        let (arg_types, decl) = {
+            let ir = self.ir.borrow();
+            let m = ir.method(method_decl);
+            let arg_types: Vec<ir::Type> = m.args.iter().map(|&a| ir.decl(a).type_).collect();
+            (arg_types, m.span)
+        };
+        let method = &t.name.name;
+        let recovered = self.recovered.borrow().contains(&method_decl);
+        if args.len() != arg_types.len() && !recovered {
+            self.error(SemanticCheckError::MethodArgumentCountMismatch {
+                method: method.clone(),
+                expected: arg_types.len(),
+                found: args.len(),
+                span: t.span,
+                decl,
+            });
+        } else {
+            // only the parameters of a recovered method that were parsed
+            for (index, (&arg, expected)) in args.iter().zip(arg_types).enumerate() {
+                self.check_type(arg, expected, |found, span| {
+                    SemanticCheckError::MethodArgumentTypeMismatch {
+                        method: method.clone(),
+                        index,
+                        expected,
+                        found,
+                        span,
+                        decl,
+                    }
+                });
             }
         }
-        Ok(ir::Method {
+        Some(ir::Method {
             decl: method_decl,
             args,
+            span: t.span,
         })
     }
 
-    fn get_ir_callout(&self, t: token::Callout) -> IRResult<ir::Callout> {
-        let mut args = Vec::new();
-        for targ in t.args {
-            let arg = match targ {
-                token::CalloutArg::Expr(e) => {
-                    ir::CalloutArg::Expr(unwrap_or_early_return!(self.get_ir_expr(e)))
-                }
-                token::CalloutArg::StringLiteral(s) => ir::CalloutArg::StringLiteral(s),
-            };
-            args.push(arg);
+    fn get_ir_callout(&self, t: token::Callout) -> ir::Callout {
+        let args = t
+            .args
+            .into_iter()
+            .map(|targ| match targ {
+                token::CalloutArg::Expr(e) => ir::CalloutArg::Expr(self.get_ir_expr(e)),
+                token::CalloutArg::StringLiteral(s, span) => ir::CalloutArg::StringLiteral(s, span),
+            })
+            .collect();
+        ir::Callout {
+            name: t.name,
+            args,
+            span: t.span,
         }
-        Ok(ir::Callout { name: t.name, args })
     }
 
-    fn get_ir_call(&self, t: token::MethodCall) -> IRResult<ir::Call> {
+    fn get_ir_call(&self, t: token::MethodCall) -> Option<ir::Call> {
         match t {
-            token::MethodCall::Method(t) => match self.get_ir_method(t) {
-                Ok(m) => Ok(ir::Call::Method(m)),
-                Err(e) => Err(e),
-            },
-            token::MethodCall::Callout(t) => match self.get_ir_callout(t) {
-                Ok(m) => Ok(ir::Call::Callout(m)),
-                Err(e) => Err(e),
-            },
+            token::MethodCall::Method(t) => self.get_ir_method(t).map(ir::Call::Method),
+            token::MethodCall::Callout(t) => Some(ir::Call::Callout(self.get_ir_callout(t))),
         }
     }
-    fn get_ir_literal(&self, t: token::Literal) -> Result<ir::Literal, Vec<SemanticCheckError>> {
-        match t {
-            token::Literal::Int(l) => Ok(ir::Literal::Int(l)),
-            token::Literal::Bool(l) => match l {
-                token::BoolLiteral::True => Ok(ir::Literal::Boolean(true)),
-                token::BoolLiteral::False => Ok(ir::Literal::Boolean(false)),
+    fn get_ir_literal(&self, t: token::Literal) -> Option<ir::Literal> {
+        let span = t.span;
+        match t.kind {
+            // out of range literals are reported by `is_int_literal_in_range`
+            token::LiteralKind::Int(l) => int_literal_value(&l, false).map(ir::Literal::Int),
+            token::LiteralKind::Bool(l) => match l {
+                token::BoolLiteral::True => Some(ir::Literal::Boolean(true)),
+                token::BoolLiteral::False => Some(ir::Literal::Boolean(false)),
             },
-            token::Literal::Char(l) => {
+            token::LiteralKind::Char(l) => {
                 if l.is_ascii() {
-                    Ok(ir::Literal::Int(u32::from(l) as i32))
+                    Some(ir::Literal::Int(u32::from(l) as i32))
                 } else {
-                    Err(vec![SemanticCheckError::NonAsciiCharLiteral(l)])
+                    self.error(SemanticCheckError::NonAsciiCharLiteral { value: l, span });
+                    None
                 }
             }
         }
     }
-    fn get_ir_unary(&self, t: token::Unary) -> IRResult<ir::Unary> {
-        let expr = unwrap_or_early_return!(self.get_ir_expr(t.expr));
-        let type_ = expr.borrow().type_;
+    fn get_ir_unary(&self, t: token::Unary) -> ir::Unary {
+        let expr = self.get_ir_expr(t.expr);
         match t.op {
-            token::UnaryOp::NegInt if type_ == ir::Type::Int => Ok(ir::Unary {
-                expr,
-                op: ir::UnaryOp::NegInt,
-            }),
-            token::UnaryOp::NegBool if type_ == ir::Type::Bool => Ok(ir::Unary {
-                expr,
-                op: ir::UnaryOp::NegBool,
-            }),
-            _ => Err(vec![SemanticCheckError::TypeMismatch(String::from(
-                "unary op is not supported",
-            ))]),
-        }
-    }
-    fn get_ir_binary(&self, t: token::Binary) -> IRResult<ir::Binary> {
-        let mut errors = Vec::new();
-        let lhs = match self.get_ir_expr(t.lhs) {
-            Ok(l) => Some(l),
-            Err(e) => {
-                errors.extend(e);
-                None
+            token::UnaryOp::NegInt => {
+                self.check_type(expr, ir::Type::Int, |found, span| {
+                    SemanticCheckError::ArithOperandNotInt { found, span }
+                });
+                ir::Unary {
+                    expr,
+                    op: ir::UnaryOp::NegInt,
+                }
             }
-        };
-        let rhs = match self.get_ir_expr(t.rhs) {
-            Ok(l) => Some(l),
-            Err(e) => {
-                errors.extend(e);
-                None
+            token::UnaryOp::NegBool => {
+                self.check_type(expr, ir::Type::Bool, |found, span| {
+                    SemanticCheckError::CondOperandNotBool { found, span }
+                });
+                ir::Unary {
+                    expr,
+                    op: ir::UnaryOp::NegBool,
+                }
             }
-        };
-
-        if !errors.is_empty() {
-            return Err(errors);
-        }
-
-        let lhs = lhs.unwrap();
-        let rhs = rhs.unwrap();
-
-        if lhs.borrow().type_ != rhs.borrow().type_ {
-            errors.push(SemanticCheckError::TypeMismatch(String::from(
-                "binary lhs rhs type is not matched",
-            )));
-            return Err(errors);
         }
+    }
+    fn get_ir_binary(&self, t: token::Binary) -> ir::Binary {
+        let lhs = self.get_ir_expr(t.lhs);
+        let rhs = self.get_ir_expr(t.rhs);
 
         let op = ir::BinaryOp::from(&t.op);
-        let operand_type = lhs.borrow().type_;
+        let (lhs_type, lhs_span) = self.expr_info(lhs);
+        let (rhs_type, rhs_span) = self.expr_info(rhs);
+        // the first operand of the wrong type, ignoring operands that
+        // failed to check since they are already reported
+        let mismatch = |expected: ir::Type| {
+            [(lhs_type, lhs_span), (rhs_type, rhs_span)]
+                .into_iter()
+                .find(|&(type_, _)| type_ != expected && type_ != ir::Type::Error)
+        };
         match op {
             // 12. The operands of <arith op>s and <rel op>s must have type int.
             ir::BinaryOp::Add
@@ -323,421 +508,350 @@ impl SemanticAnalyzer {
             | ir::BinaryOp::GT
             | ir::BinaryOp::GE
             | ir::BinaryOp::LT
-            | ir::BinaryOp::LE
-                if operand_type == ir::Type::Int => {}
+            | ir::BinaryOp::LE => {
+                if let Some((found, span)) = mismatch(ir::Type::Int) {
+                    self.error(SemanticCheckError::ArithOperandNotInt { found, span });
+                }
+            }
             // 13. The operands of <eq op>s must have the same
             //     type, either int or boolean.
-            ir::BinaryOp::EQ | ir::BinaryOp::NE => (),
+            ir::BinaryOp::EQ | ir::BinaryOp::NE => {
+                if lhs_type != rhs_type && lhs_type != ir::Type::Error && rhs_type != ir::Type::Error {
+                    self.error(SemanticCheckError::EqOperandTypeMismatch {
+                        lhs: lhs_type,
+                        rhs: rhs_type,
+                        span: t.span,
+                    });
+                }
+            }
             // 14. The operands of <cond op>s and the operand of
             //     logical not (!) must have type boolean.
-            ir::BinaryOp::Or | ir::BinaryOp::And if operand_type == ir::Type::Bool => (),
-
-            // Otherwise, type mismatch error
-            _ => {
-                errors.push(SemanticCheckError::TypeMismatch(format!(
-                    "binary op {:?} is not supported: \nlhs: {:?}\nrhs: {:?}",
-                    op, lhs, rhs
-                )));
-                return Err(errors);
+            ir::BinaryOp::Or | ir::BinaryOp::And => {
+                if let Some((found, span)) = mismatch(ir::Type::Bool) {
+                    self.error(SemanticCheckError::CondOperandNotBool { found, span });
+                }
             }
         }
-        Ok(ir::Binary { lhs, rhs, op })
+        ir::Binary { lhs, rhs, op }
     }
 
-    fn get_ir_expr(&self, t: token::Expr) -> IRResult<ir::Expr> {
+    #[allow(clippy::boxed_local)]
+    fn get_ir_expr(&self, t: token::Expr) -> ir::ExprId {
+        let span = t.span();
         let expr_type = match *t {
-            token::Expr0::Location(t) => match self.get_ir_location(t) {
-                Ok(a) => Ok(ir::ExprType::Location(a)),
-                Err(e) => Err(e),
-            },
-            token::Expr0::MethodCall(t) => match self.get_ir_call(t) {
-                Ok(a) => Ok(ir::ExprType::Call(a)),
-                Err(e) => Err(e),
-            },
-            token::Expr0::Literal(t) => match self.get_ir_literal(t) {
-                Ok(a) => Ok(ir::ExprType::Literal(a)),
-                Err(e) => Err(e),
-            },
-            token::Expr0::Unary(t) => match self.get_ir_unary(t) {
-                Ok(a) => Ok(ir::ExprType::Unary(a)),
-                Err(e) => Err(e),
-            },
-            token::Expr0::Binary(t) => match self.get_ir_binary(t) {
-                Ok(a) => Ok(ir::ExprType::Binary(a)),
-                Err(e) => Err(e),
+            token::Expr0::Location(t) => self.get_ir_location(t).map(ir::ExprType::Location),
+            token::Expr0::MethodCall(t) => self.get_ir_call(t).map(ir::ExprType::Call),
+            token::Expr0::Literal(t) => self.get_ir_literal(t).map(ir::ExprType::Literal),
+            // the minimum int is only in range as a whole, so negated
+            // literals are folded
+            token::Expr0::Unary(t) => match negated_int_literal(&t) {
+                Some(l) => int_literal_value(l, true).map(|n| ir::ExprType::Literal(ir::Literal::Int(n))),
+                None => Some(ir::ExprType::Unary(self.get_ir_unary(t))),
             },
+            token::Expr0::Binary(t) => Some(ir::ExprType::Binary(self.get_ir_binary(t))),
         };
         let expr_type = match expr_type {
-            Ok(e) => e,
-            Err(e) => return Err(e),
+            Some(e) => e,
+            None => return self.error_expr(span),
         };
 
-        let type_ = get_ir_expr_type(&expr_type);
+        let type_ = get_ir_expr_type(&expr_type, span, &self.ir.borrow());
         let type_ = match type_ {
             Ok(t) => t,
-            Err(e) => return Err(vec![e]),
-        };
-        Ok(create_rc(ir::Expr0 {
-            type_,
-            expr: expr_type,
-        }))
-    }
-
-    fn get_ir_assign(&self, t: token::Assign) -> Result<ir::Assign, Vec<SemanticCheckError>> {
-        let mut errors = Vec::new();
-        let dst = match self.get_ir_location(t.dst) {
-            Ok(d) => Some(d),
             Err(e) => {
-                errors.extend(e);
-                None
+                self.error(e);
+                ir::Type::Error
             }
         };
+        self.add_expr(type_, expr_type, span)
+    }
+
+    /// `None` if the destination does not name a variable
+    fn get_ir_assign(&self, t: token::Assign) -> Option<ir::Assign> {
+        let dst = self.get_ir_location(t.dst);
         let op = ir::AssignOp::from(t.op);
-        let val = match self.get_ir_expr(t.val) {
-            Ok(d) => Some(d),
-            Err(e) => {
-                errors.extend(e);
-                None
+        let val = self.get_ir_expr(t.val);
+        let dst = dst?;
+        let decl = self.decl(dst.decl);
+        let dst_type = decl.type_;
+        let (val_type, _) = self.expr_info(val);
+        if op != ir::AssignOp::Assign {
+            let found = if dst_type != ir::Type::Int { dst_type } else { val_type };
+            if found != ir::Type::Int && found != ir::Type::Error {
+                self.error(SemanticCheckError::CompoundAssignNotInt { found, span: t.span });
             }
-        };
-        if !errors.is_empty() {
-            return Err(errors);
-        }
-        let dst = dst.unwrap();
-        let val = val.unwrap();
-        let dst_type = dst.decl.borrow().type_;
-        let val_type = val.borrow().type_;
-        if dst_type != val_type {
-            errors.push(SemanticCheckError::TypeMismatch(String::from(
-                "assign type not match",
-            )));
-            return Err(errors);
-        }
-
-        if op != ir::AssignOp::Assign && dst_type != ir::Type::Int {
-            errors.push(SemanticCheckError::TypeMismatch(String::from(
-                "non-int assign",
-            )));
-            return Err(errors);
-        }
-
-        Ok(ir::Assign { dst, op, val })
+        } else if dst_type != val_type && val_type != ir::Type::Error {
+            self.error(SemanticCheckError::AssignTypeMismatch {
+                name: decl.name,
+                expected: dst_type,
+                found: val_type,
+                span: t.span,
+                decl: decl.span,
+            });
+        }
+
+        Some(ir::Assign {
+            dst,
+            op,
+            val,
+            span: t.span,
+        })
     }
 
-    fn get_ir_ifelse(&self, t: token::IfElse) -> IRResult<ir::IfElse> {
-        let cond = match self.get_ir_expr(t.cond) {
-            Ok(c) => c,
-            Err(e) => return Err(e),
-        };
-        check_type_or_early_return!(cond, ir::Type::Bool);
-        let ifelse = create_rc(ir::IfElse0 {
-            cond,
-            true_block: None,
-            false_block: None,
+    fn get_ir_ifelse(&self, t: token::IfElse) -> ir::IfElse {
+        let cond = self.get_ir_expr(t.cond);
+        self.check_type(cond, ir::Type::Bool, |found, span| {
+            SemanticCheckError::IfConditionNotBool { found, span }
         });
-        let true_block = self.get_ir_block(t.true_block, EnvType::If(ifelse.clone()));
-        let true_block = Some(unwrap_or_early_return!(true_block));
-        let false_block = match t.false_block {
-            Some(b) => {
-                let fb = self.get_ir_block(b, EnvType::Else(ifelse.clone()));
-                Some(unwrap_or_early_return!(fb))
-            }
-            None => None,
+        let true_block = self.get_ir_block(t.true_block, EnvType::If);
+        let false_block = t.false_block.map(|b| self.get_ir_block(b, EnvType::Else));
+        ir::IfElse {
+            cond,
+            true_block,
+            false_block,
+            span: t.span,
+        }
+    }
+    fn get_ir_return(&self, t: token::Return) -> ir::Return {
+        let val = t.val.map(|e| self.get_ir_expr(e));
+        let env_ctx = EnvContext::new(self.envs.clone(), EnvType::NoEnv);
+        let func = env_ctx.get_current_scope_method_decl().unwrap();
+        let (decl_return_type, method, decl_span) = {
+            let ir = self.ir.borrow();
+            let m = ir.method(func);
+            (m.return_type, m.name.clone(), m.span)
         };
-        ifelse.borrow_mut().true_block = true_block;
-        ifelse.borrow_mut().false_block = false_block;
-        assert!(ifelse.borrow().true_block.is_some());
-        Ok(ifelse)
-    }
-    fn get_ir_return(&self, t: token::Return) -> IRResult<ir::Return> {
-        let val = match t.val {
-            Some(e) => match self.get_ir_expr(e) {
-                Ok(e) => Some(e),
-                Err(e) => return Err(e),
-            },
-            None => None,
+        let mismatch = |found| SemanticCheckError::ReturnTypeMismatch {
+            method: method.clone(),
+            expected: decl_return_type,
+            found,
+            span: t.span,
+            decl: decl_span,
         };
-        let env_ctx = EnvContext::new(self.envs.clone(), EnvType::NoEnv);
-        let decl = env_ctx.get_current_scope_method_decl().unwrap();
-        let decl_return_type = decl.borrow().return_type;
         match val {
-            // non-void return type: declared type and expr type should match
-            Some(v)
-                if decl_return_type != ir::Type::Void && decl_return_type == v.borrow().type_ =>
-            {
-                Ok(ir::Return {
-                    func: decl,
-                    val: Some(v),
+            // void return type: declared type is void and expr should none
+            Some(_) if decl_return_type == ir::Type::Void => {
+                self.error(SemanticCheckError::ReturnValueInVoidMethod {
+                    method: method.clone(),
+                    span: t.span,
+                    decl: decl_span,
                 })
             }
-            // void return type: declared type is void and expr should none
-            None if decl_return_type == ir::Type::Void => Ok(ir::Return {
-                func: decl,
-                val: None,
-            }),
-            _ => Err(vec![SemanticCheckError::ReturnTypeMismatch]),
+            // non-void return type: declared type and expr type should match
+            Some(v) => self.check_type(v, decl_return_type, |found, _| mismatch(found)),
+            None if decl_return_type != ir::Type::Void => self.error(mismatch(ir::Type::Void)),
+            None => (),
+        }
+        ir::Return {
+            func,
+            val,
+            span: t.span,
         }
     }
-    fn get_ir_break(&self) -> Result<ir::Break, Vec<SemanticCheckError>> {
+    fn get_ir_break(&self, span: Span) -> Option<ir::Break> {
         let env_ctx = EnvContext::new(self.envs.clone(), EnvType::NoEnv);
-        let for_ = match env_ctx.find_for() {
-            Some(f) => f,
-            None => return Err(vec![SemanticCheckError::BreakOutOfForScope]),
-        };
-        Ok(ir::Break { for_ })
+        match env_ctx.find_for() {
+            Some(for_) => Some(ir::Break { for_, span }),
+            None => {
+                self.error(SemanticCheckError::BreakOutOfForScope { span });
+                None
+            }
+        }
     }
-    fn get_ir_continue(&self) -> Result<ir::Continue, Vec<SemanticCheckError>> {
+    fn get_ir_continue(&self, span: Span) -> Option<ir::Continue> {
         let env_ctx = EnvContext::new(self.envs.clone(), EnvType::NoEnv);
-        let for_ = match env_ctx.find_for() {
-            Some(f) => f,
-            None => return Err(vec![SemanticCheckError::ContinueOutOfForScope]),
-        };
-        Ok(ir::Continue { for_ })
-    }
-    fn get_ir_for(&self, t: token::Loop) -> Result<ir::For, Vec<SemanticCheckError>> {
-        let start = unwrap_or_early_return!(self.get_ir_expr(t.start));
-        check_type_or_early_return!(start, ir::Type::Int);
-        let end = unwrap_or_early_return!(self.get_ir_expr(t.end));
-        check_type_or_early_return!(end, ir::Type::Int);
-        let index_decl = create_rc(ir::VarDecl0 {
+        match env_ctx.find_for() {
+            Some(for_) => Some(ir::Continue { for_, span }),
+            None => {
+                self.error(SemanticCheckError::ContinueOutOfForScope { span });
+                None
+            }
+        }
+    }
+    /// fill the statement `id` reserved for the loop `t`, so that `break`
+    /// and `continue` can refer to it
+    fn get_ir_for(&self, t: token::Loop, id: ir::StmtId) {
+        let start = self.get_ir_expr(t.start);
+        self.check_type(start, ir::Type::Int, |found, span| {
+            SemanticCheckError::ForBoundNotInt { found, span }
+        });
+        let end = self.get_ir_expr(t.end);
+        self.check_type(end, ir::Type::Int, |found, span| {
+            SemanticCheckError::ForBoundNotInt { found, span }
+        });
+        let index_decl = self.ir.borrow_mut().add_decl(ir::VarDecl {
             type_: ir::Type::Int,
-            name: t.index_var,
+            name: t.index_var.name,
             arr_size: None,
+            span: t.index_var.span,
         });
-        let for_ = create_rc(ir::For0 {
-            index_decl: index_decl.clone(),
+        let env_ctx = EnvContext::new(self.envs.clone(), EnvType::For(id));
+        let _scope = self.enter_scope(ScopeKind::For, t.block.span);
+        self.symbols.borrow_mut().declare_var(index_decl, &self.decl(index_decl), SymbolKind::LoopIndex, t.index_var.span);
+        if let Err(e) = self.add_var(&env_ctx, index_decl) {
+            self.error(self.duplicated(index_decl, e));
+        }
+
+        // the loop is visible to its body, for `break`, `continue` and
+        // redeclarations of the index
+        let empty = ir::Block {
+            var_decls: Vec::new(),
+            statements: Vec::new(),
+            span: t.block.span,
+        };
+        *self.ir.borrow_mut().stmt_mut(id) = ir::Statement::For(ir::For {
+            index_decl,
             start,
             end,
-            block: None,
+            block: empty,
+            span: t.span,
         });
-        let env_ctx = EnvContext::new(self.envs.clone(), EnvType::For(for_.clone()));
-        if env_ctx.add_var(&index_decl).is_err() {
-            let e = SemanticCheckError::DuplicatedSymbol(index_decl.borrow().name.clone());
-            return Err(vec![e]);
-        }
-
-        let block = unwrap_or_early_return!(self.get_ir_block(t.block, EnvType::NoEnv));
-        for_.borrow_mut().block = Some(block);
-        assert!(for_.borrow().block.is_some());
-        Ok(for_)
-    }
-    fn get_ir_statement(
-        &self,
-        t: token::Statement,
-    ) -> Result<ir::Statement, Vec<SemanticCheckError>> {
-        match t {
-            token::Statement::Assign(a) => match self.get_ir_assign(a) {
-                Ok(a) => Ok(create_rc(ir::Statement0::Assign(a))),
-                Err(e) => Err(e),
-            },
-            token::Statement::MethodCall(a) => match self.get_ir_call(a) {
-                Ok(a) => Ok(create_rc(ir::Statement0::Call(a))),
-                Err(e) => Err(e),
-            },
-            token::Statement::IfElse(a) => match self.get_ir_ifelse(a) {
-                Ok(a) => Ok(create_rc(ir::Statement0::IfElse(a))),
-                Err(e) => Err(e),
-            },
-            token::Statement::Loop(a) => match self.get_ir_for(a) {
-                Ok(a) => Ok(create_rc(ir::Statement0::For(a))),
-                Err(e) => Err(e),
-            },
-            token::Statement::Return(a) => match self.get_ir_return(a) {
-                Ok(a) => Ok(create_rc(ir::Statement0::Return(a))),
-                Err(e) => Err(e),
-            },
-            token::Statement::Break => match self.get_ir_break() {
-                Ok(a) => Ok(create_rc(ir::Statement0::Break(a))),
-                Err(e) => Err(e),
-            },
-            token::Statement::Continue => match self.get_ir_continue() {
-                Ok(a) => Ok(create_rc(ir::Statement0::Continue(a))),
-                Err(e) => Err(e),
-            },
-            token::Statement::Block(a) => match self.get_ir_block(a, EnvType::Anon) {
-                Ok(a) => Ok(create_rc(ir::Statement0::Block(a))),
-                Err(e) => Err(e),
-            },
+        let block = self.get_ir_block(t.block, EnvType::NoEnv);
+        if let ir::Statement::For(f) = self.ir.borrow_mut().stmt_mut(id) {
+            f.block = block;
         }
     }
-
-    fn get_ir_statements(
-        &self,
-        t: Vec<token::Statement>,
-    ) -> Result<Vec<ir::Statement>, Vec<SemanticCheckError>> {
-        let mut errors: Vec<SemanticCheckError> = Vec::new();
-        let mut statements: Vec<ir::Statement> = Vec::new();
-
-        for stmt in t {
-            let ir_stmt = self.get_ir_statement(stmt);
-            match ir_stmt {
-                Ok(s) => statements.push(s),
-                Err(e) => errors.extend(e),
+    /// `None` if the statement cannot be represented in the IR
+    fn get_ir_statement(&self, t: token::Statement) -> Option<ir::StmtId> {
+        let s = match t {
+            token::Statement::Assign(a) => ir::Statement::Assign(self.get_ir_assign(a)?),
+            token::Statement::MethodCall(a) => ir::Statement::Call(self.get_ir_call(a)?),
+            token::Statement::IfElse(a) => ir::Statement::IfElse(self.get_ir_ifelse(a)),
+            token::Statement::Loop(a) => {
+                let id = self.add_stmt(ir::Statement::Block(ir::Block {
+                    var_decls: Vec::new(),
+                    statements: Vec::new(),
+                    span: a.span,
+                }));
+                self.get_ir_for(a, id);
+                return Some(id);
             }
-        }
-
-        if !errors.is_empty() {
-            Err(errors)
-        } else {
-            Ok(statements)
-        }
+            token::Statement::Return(a) => ir::Statement::Return(self.get_ir_return(a)),
+            token::Statement::Break(span) => ir::Statement::Break(self.get_ir_break(span)?),
+            token::Statement::Continue(span) => ir::Statement::Continue(self.get_ir_continue(span)?),
+            token::Statement::Block(a) => ir::Statement::Block(self.get_ir_block(a, EnvType::Anon)),
+        };
+        Some(self.add_stmt(s))
     }
 
-    fn get_ir_block(
-        &self,
-        t: token::Block,
-        scope_type: EnvType,
-    ) -> Result<ir::Block, Vec<SemanticCheckError>> {
+    fn get_ir_block(&self, t: token::Block, scope_type: EnvType) -> ir::Block {
+        let _scope = match scope_type {
+            EnvType::NoEnv => None,
+            _ => Some(self.enter_scope(ScopeKind::Block, t.span)),
+        };
         let _env_ctx = EnvContext::new(self.envs.clone(), scope_type);
-        let mut errors = Vec::new();
 
         // variable declations
         let var_decls = self.get_ir_var_decls(t.var_decls);
-        let var_decls = match var_decls {
-            Ok(v) => Some(v),
-            Err(e) => {
-                errors.extend(e);
-                None
-            }
-        };
 
         // statements in this block
-        let statements = self.get_ir_statements(t.statements);
-        let statements = match statements {
-            Ok(s) => Some(s),
-            Err(e) => {
-                errors.extend(e);
-                None
-            }
-        };
-        if !errors.is_empty() {
-            Err(errors)
-        } else {
-            let b = ir::Block {
-                var_decls: var_decls.unwrap(),
-                statements: statements.unwrap(),
-            };
-            Ok(b)
+        let statements = t
+            .statements
+            .into_iter()
+            .filter_map(|s| self.get_ir_statement(s))
+            .collect();
+        ir::Block {
+            var_decls,
+            statements,
+            span: t.span,
         }
     }
 
-    fn get_ir_method_decl(&self, t: token::MethodDecl) -> IRResult<ir::MethodDecl> {
-        let mut errors = Vec::new();
+    fn get_ir_method_decl(&self, t: token::MethodDecl) -> ir::MethodId {
         let return_type = ir::Type::from(&t.return_type);
-        let args: Vec<ir::VarDecl> = t.args.iter().map(|a| self.get_ir_method_arg(a)).collect();
+        let args: Vec<ir::DeclId> = t.args.iter().map(|a| self.get_ir_method_arg(a)).collect();
 
-        let ir_decl = ir::MethodDecl0 {
+        // the body is built once the method can be referred to
+        let ir_decl = ir::MethodDecl {
             return_type,
-            name: t.name,
-            args,
-            block: None,
+            name: t.name.name,
+            args: args.clone(),
+            block: ir::Block {
+                var_decls: Vec::new(),
+                statements: Vec::new(),
+                span: t.block.span,
+            },
+            needs_fall_off_trap: false,
+            span: t.span,
         };
-        let ir_decl = create_rc(ir_decl);
+        let name = ir_decl.name.clone();
+        let id = self.ir.borrow_mut().add_method(ir_decl);
+        if t.recovered {
+            self.recovered.borrow_mut().insert(id);
+        }
+
+        // fields and methods share the global scope
+        let global_ctx = EnvContext::new(self.envs.clone(), EnvType::NoEnv);
+        if let Some(field) = global_ctx.find_var_decl(&name) {
+            self.error(SemanticCheckError::MethodNameIsField {
+                name: name.clone(),
+                span: t.name.span,
+                field: self.decl(field).span,
+            });
+        }
 
-        let env_ctx = EnvContext::new(self.envs.clone(), EnvType::Method(ir_decl.clone()));
+        self.symbols.borrow_mut().declare_method(id, self.ir.borrow().method(id), t.name.span);
+        let env_ctx = EnvContext::new(self.envs.clone(), EnvType::Method(id));
+        let _scope = self.enter_scope(ScopeKind::Method, t.span);
 
         // add method argument to symbol table
-        for arg in &ir_decl.borrow().args {
-            if env_ctx.add_var(arg).is_err() {
-                let e = SemanticCheckError::DuplicatedSymbol(arg.borrow().name.clone());
-                return Err(vec![e]);
+        for &arg in &args {
+            let d = self.decl(arg);
+            self.symbols.borrow_mut().declare_var(arg, &d, SymbolKind::Parameter, d.span);
+            if let Err(e) = self.add_var(&env_ctx, arg) {
+                self.error(self.duplicated(arg, e));
             }
         }
 
         // add method declaration for recursive call
-        if env_ctx.add_method(&ir_decl).is_err() {
-            let e = SemanticCheckError::DuplicatedSymbol(ir_decl.borrow().name.clone());
-            errors.push(e);
+        if let Err(e) = env_ctx.add_method(&name, id) {
+            let span = self.ir.borrow().method(id).span;
+            self.error(SemanticCheckError::DuplicatedSymbol {
+                name,
+                span,
+                prev: e.prev_span(&self.ir.borrow()),
+            });
         }
 
         // symbol table should have method arguments
         let block = self.get_ir_block(t.block, EnvType::NoEnv);
-        if let Err(e) = block {
-            errors.extend(e);
-        } else {
-            ir_decl.borrow_mut().block = Some(block.ok().unwrap());
-        }
-
-        if errors.is_empty() {
-            Ok(ir_decl)
-        } else {
-            Err(errors)
-        }
-    }
-
-    fn get_ir_method_decls(
-        &self,
-        decls: Vec<token::MethodDecl>,
-    ) -> Result<Vec<ir::MethodDecl>, Vec<SemanticCheckError>> {
-        let mut methods = Vec::new();
-        let mut errors = Vec::new();
-        for decl in decls {
-            match self.get_ir_method_decl(decl) {
-                Ok(d) => methods.push(d),
-                Err(e) => errors.extend(e),
-            }
-        }
-        if !errors.is_empty() {
-            Err(errors)
-        } else {
-            Ok(methods)
-        }
+        let mut ir = self.ir.borrow_mut();
+        let needs_fall_off_trap = return_type != ir::Type::Void && !always_returns(&block, &ir);
+        let m = ir.method_mut(id);
+        m.needs_fall_off_trap = needs_fall_off_trap;
+        m.block = block;
+        id
     }
 
-    fn construct_ir(&self, p: token::Program) -> Result<ir::IRRoot, Vec<SemanticCheckError>> {
+    fn construct_ir(&self, p: token::Program) -> ir::IRRoot {
+        *self.ir.borrow_mut() = ir::IRRoot::default();
+        self.recovered.borrow_mut().clear();
         let _env_ctx = EnvContext::new(self.envs.clone(), EnvType::Global);
-        let mut errors = Vec::new();
+        let _scope = self.enter_scope(ScopeKind::Global, p.span);
         let field_decls = self.get_ir_field_decls(p.field_decls);
-        let field_decls = match field_decls {
-            Err(e) => {
-                errors.extend(e);
-                None
-            }
-            Ok(m) => Some(m),
-        };
-        let method_decls = self.get_ir_method_decls(p.method_decls);
-        let method_decls = match method_decls {
-            Err(e) => {
-                errors.extend(e);
-                None
-            }
-            Ok(m) => Some(m),
-        };
-        if !errors.is_empty() {
-            return Err(errors);
+        for m in &p.method_decls {
+            self.method_names.borrow_mut().entry(m.name.name.clone()).or_insert(m.span);
         }
-        let program_decl = ir::ProgramClassDecl {
-            field_decls: field_decls.unwrap(),
-            method_decls: method_decls.unwrap(),
-        };
-        let root = ir::IRRoot { root: program_decl };
-        Ok(root)
-    }
-    fn pre_ir_check(&self, p: &token::Program) -> Result<(), Vec<SemanticCheckError>> {
-        let passes = Vec::from([
-            /* pass 3 */ has_main,
-            /* pass 4 */ is_array_size_positive,
-        ]);
-        let errors: Vec<SemanticCheckError> = passes
-            .iter()
-            .map(|&pass| pass(p))
-            .filter(|res| res.is_err())
-            .map(|res| res.err().unwrap())
+        let method_decls = p
+            .method_decls
+            .into_iter()
+            .map(|d| self.get_ir_method_decl(d))
             .collect();
-        if errors.is_empty() {
-            Ok(())
-        } else {
-            Err(errors)
-        }
+        let mut root = self.ir.take();
+        root.root = ir::ProgramClassDecl {
+            field_decls,
+            method_decls,
+            span: p.span,
+        };
+        root
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::parser::DecafParser;
+    use crate::parser::parse;
     use crate::test_util::get_current_dir;
-    use std::env;
     use std::fs::read_to_string;
     use std::path::PathBuf;
 
@@ -750,11 +864,25 @@ mod tests {
                     .iter()
                     .collect();
                 let s = read_to_string(&path).unwrap();
-                let program = DecafParser::new().parse(&s).unwrap();
+                let program = parse(&s).unwrap();
                 let res = SemanticAnalyzer::new().create_ir(program);
                 assert!(res.is_err());
             }
         };
+        ( $testname:ident, $filename:expr, $($code:expr),+ ) => {
+            #[test]
+            fn $testname() {
+                let path = get_current_dir();
+                let path: PathBuf = [&path, "src", "semantic_analyzer", "testcases", $filename]
+                    .iter()
+                    .collect();
+                let s = read_to_string(&path).unwrap();
+                let program = parse(&s).unwrap();
+                let errors = SemanticAnalyzer::new().create_ir(program).err().unwrap();
+                let codes: Vec<&str> = errors.iter().map(|e| e.code()).collect();
+                assert_eq!(codes, vec![$($code),+]);
+            }
+        };
     }
 
     macro_rules! test_sa_legal {
@@ -766,7 +894,7 @@ mod tests {
                     .iter()
                     .collect();
                 let s = read_to_string(&path).unwrap();
-                let program = DecafParser::new().parse(&s).unwrap();
+                let program = parse(&s).unwrap();
                 let res = SemanticAnalyzer::new().create_ir(program);
                 // let errors = res.err().unwrap();
                 // for err in errors {
@@ -778,22 +906,99 @@ mod tests {
         };
     }
 
-    test_sa_illegal!(test_sa_illegal_01, "illegal-01.dcf");
-    test_sa_illegal!(test_sa_illegal_02, "illegal-02.dcf");
-    test_sa_illegal!(test_sa_illegal_03, "illegal-03.dcf");
-    test_sa_illegal!(test_sa_illegal_04, "illegal-04.dcf");
-    test_sa_illegal!(test_sa_illegal_05, "illegal-05.dcf");
-    test_sa_illegal!(test_sa_illegal_06, "illegal-06.dcf");
-    test_sa_illegal!(test_sa_illegal_07, "illegal-07.dcf");
-    test_sa_illegal!(test_sa_illegal_08, "illegal-08.dcf");
-    test_sa_illegal!(test_sa_illegal_09, "illegal-09.dcf");
-    test_sa_illegal!(test_sa_illegal_10, "illegal-10.dcf");
-    test_sa_illegal!(test_sa_illegal_11, "illegal-11.dcf");
-    test_sa_illegal!(test_sa_illegal_12, "illegal-12.dcf");
-    test_sa_illegal!(test_sa_illegal_13, "illegal-13.dcf");
-    test_sa_illegal!(test_sa_illegal_14, "illegal-14.dcf");
-    test_sa_illegal!(test_sa_illegal_15, "illegal-15.dcf");
-    test_sa_illegal!(test_sa_illegal_16, "illegal-16.dcf");
-    test_sa_illegal!(test_sa_illegal_17, "illegal-17.dcf");
+    test_sa_illegal!(test_sa_illegal_01, "illegal-01.dcf", "E0001");
+    test_sa_illegal!(test_sa_illegal_02, "illegal-02.dcf", "E0002");
+    test_sa_illegal!(test_sa_illegal_03, "illegal-03.dcf", "E0003");
+    test_sa_illegal!(test_sa_illegal_04, "illegal-04.dcf", "E0004");
+    test_sa_illegal!(test_sa_illegal_05, "illegal-05.dcf", "E0005");
+    test_sa_illegal!(test_sa_illegal_06, "illegal-06.dcf", "E0108");
+    test_sa_illegal!(test_sa_illegal_07, "illegal-07.dcf", "E0007");
+    test_sa_illegal!(test_sa_illegal_08, "illegal-08.dcf", "E0008");
+    test_sa_illegal!(test_sa_illegal_09, "illegal-09.dcf", "E0109");
+    test_sa_illegal!(test_sa_illegal_10, "illegal-10.dcf", "E0102");
+    test_sa_illegal!(test_sa_illegal_11, "illegal-11.dcf", "E0011");
+    test_sa_illegal!(test_sa_illegal_12, "illegal-12.dcf", "E0017");
+    test_sa_illegal!(test_sa_illegal_13, "illegal-13.dcf", "E0015");
+    test_sa_illegal!(test_sa_illegal_14, "illegal-14.dcf", "E0012");
+    test_sa_illegal!(test_sa_illegal_15, "illegal-15.dcf", "E0013");
+    test_sa_illegal!(test_sa_illegal_16, "illegal-16.dcf", "E0014");
+    test_sa_illegal!(test_sa_illegal_17, "illegal-17.dcf", "E0016");
+    test_sa_illegal!(test_sa_illegal_18, "illegal-18.dcf", "E0103", "E0103", "E0103", "E0103");
+    test_sa_illegal!(test_sa_illegal_19, "illegal-19.dcf", "E0006");
+    test_sa_illegal!(test_sa_illegal_20, "illegal-20.dcf", "E0009");
+    test_sa_illegal!(test_sa_illegal_21, "illegal-21.dcf", "E0010");
+    test_sa_illegal!(test_sa_illegal_22, "illegal-22.dcf", "E0018");
+    test_sa_illegal!(test_sa_illegal_23, "illegal-23.dcf", "E0104");
+    test_sa_illegal!(test_sa_illegal_24, "illegal-24.dcf", "E0105");
+    test_sa_illegal!(test_sa_illegal_25, "illegal-25.dcf", "E0106");
+    test_sa_illegal!(test_sa_illegal_26, "illegal-26.dcf", "E0107");
+    test_sa_illegal!(test_sa_illegal_27, "illegal-27.dcf", "E0002", "E0002", "E0002", "E0108", "E0015");
+    test_sa_illegal!(test_sa_illegal_28, "illegal-28.dcf", "E0110");
+    test_sa_illegal!(test_sa_illegal_29, "illegal-29.dcf", "E0004", "E0004", "E0103");
     test_sa_legal!(test_sa_legal_01, "legal-01.dcf");
+    test_sa_legal!(test_sa_legal_02, "legal-02.dcf");
+
+    #[test]
+    fn test_error_data() {
+        let src = "class Program { int f(int a) { return a; } void main() { int x; x = f(true); } }";
+        let errors = SemanticAnalyzer::new().create_ir(parse(src).unwrap()).err().unwrap();
+        match &errors[..] {
+            [e @ SemanticCheckError::MethodArgumentTypeMismatch {
+                index: 0,
+                expected: ir::Type::Int,
+                found: ir::Type::Bool,
+                span,
+                decl,
+                ..
+            }] => {
+                assert_eq!(&src[span.start..span.end], "true");
+                assert!(src[decl.start..decl.end].starts_with("int f(int a)"));
+                assert_eq!(e.to_string(), "argument 1 of `f` has type boolean, expected int");
+            }
+            _ => panic!("unexpected errors: {:?}", errors),
+        }
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let src = "class Program { int count, a[2]; void foo() {} \
+                   void main() { int total; cont = 1; total = Total; foa(); printf(); total = fooo; y = 1; } }";
+        let errors = SemanticAnalyzer::new().create_ir(parse(src).unwrap()).err().unwrap();
+        let suggestions: Vec<Option<Suggestion>> = errors
+            .into_iter()
+            .map(|e| match e {
+                SemanticCheckError::UnknownSymbol { suggestion, .. } => suggestion,
+                e => panic!("unexpected error: {:?}", e),
+            })
+            .collect();
+        assert_eq!(
+            suggestions,
+            vec![
+                Some(Suggestion::Similar(String::from("count"))),
+                Some(Suggestion::Similar(String::from("total"))),
+                Some(Suggestion::Similar(String::from("foo"))),
+                Some(Suggestion::Callout),
+                Some(Suggestion::Method(String::from("foo"))),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ir_ids() {
+        fn send_sync<T: Send + Sync>(_: &T) {}
+        let src = "class Program { int n; int f() { for i = 0, n { break; } return n; } void main() { f(); } }";
+        let ir = SemanticAnalyzer::new().create_ir(parse(src).unwrap()).unwrap();
+        send_sync(&ir);
+        let (f, decl) = ir.method_decls().next().unwrap();
+        let (for_, ret) = (decl.block.statements[0], decl.block.statements[1]);
+        match (ir.stmt(for_), ir.stmt(ret)) {
+            (ir::Statement::For(l), ir::Statement::Return(r)) => {
+                assert_eq!(ir.decl(l.index_decl).name, "i");
+                assert!(matches!(ir.stmt(l.block.statements[0]), ir::Statement::Break(b) if b.for_ == for_));
+                assert_eq!(r.func, f);
+            }
+            s => panic!("unexpected statements: {:?}", s),
+        }
+    }
 }
@@ -0,0 +1,146 @@
+//! range check of int literals
+
+use super::SemanticCheckError;
+use crate::span::Span;
+use crate::token::*;
+
+/// Value of an int literal, negated if it is the operand of a unary minus,
+/// if it fits in 32 bits
+///
+/// Hex literals are magnitudes like decimal ones, so `0xffffffff` is out of
+/// range while `-0x80000000` is the minimum int.
+pub fn int_literal_value(s: &str, negated: bool) -> Option<i32> {
+    let value = match s.strip_prefix("0x") {
+        Some(h) => i64::from_str_radix(h, 16).ok()?,
+        None => s.parse::<i64>().ok()?,
+    };
+    let value = if negated { -value } else { value };
+    i32::try_from(value).ok()
+}
+
+/// Text of the int literal negated by a unary minus, if any
+pub fn negated_int_literal(u: &Unary) -> Option<&IntLiteral> {
+    match (&u.op, &*u.expr) {
+        (
+            UnaryOp::NegInt,
+            Expr0::Literal(Literal {
+                kind: LiteralKind::Int(s),
+                ..
+            }),
+        ) => Some(s),
+        _ => None,
+    }
+}
+
+fn check_literal(s: &str, negated: bool, span: Span, errors: &mut Vec<SemanticCheckError>) {
+    if int_literal_value(s, negated).is_none() {
+        let literal = if negated { format!("-{}", s) } else { s.to_string() };
+        errors.push(SemanticCheckError::IntLiteralOutOfRange { literal, span });
+    }
+}
+
+fn check_expr(e: &Expr0, errors: &mut Vec<SemanticCheckError>) {
+    match e {
+        Expr0::Location(l) => check_location(l, errors),
+        Expr0::MethodCall(c) => check_call(c, errors),
+        Expr0::Literal(Literal {
+            kind: LiteralKind::Int(s),
+            span,
+        }) => check_literal(s, false, *span, errors),
+        Expr0::Literal(_) => (),
+        // `-2147483648` is only valid as a whole
+        Expr0::Unary(u) => match negated_int_literal(u) {
+            Some(s) => check_literal(s, true, u.span, errors),
+            None => check_expr(&u.expr, errors),
+        },
+        Expr0::Binary(b) => {
+            check_expr(&b.lhs, errors);
+            check_expr(&b.rhs, errors);
+        }
+    }
+}
+
+fn check_location(l: &Location, errors: &mut Vec<SemanticCheckError>) {
+    if let Some(e) = &l.arr_size {
+        check_expr(e, errors);
+    }
+}
+
+fn check_call(c: &MethodCall, errors: &mut Vec<SemanticCheckError>) {
+    match c {
+        MethodCall::Method(m) => m.args.iter().for_each(|e| check_expr(e, errors)),
+        MethodCall::Callout(c) => {
+            for arg in &c.args {
+                if let CalloutArg::Expr(e) = arg {
+                    check_expr(e, errors);
+                }
+            }
+        }
+    }
+}
+
+fn check_block(b: &Block, errors: &mut Vec<SemanticCheckError>) {
+    for s in &b.statements {
+        match s {
+            Statement::Assign(a) => {
+                check_location(&a.dst, errors);
+                check_expr(&a.val, errors);
+            }
+            Statement::MethodCall(c) => check_call(c, errors),
+            Statement::IfElse(i) => {
+                check_expr(&i.cond, errors);
+                check_block(&i.true_block, errors);
+                if let Some(b) = &i.false_block {
+                    check_block(b, errors);
+                }
+            }
+            Statement::Loop(l) => {
+                check_expr(&l.start, errors);
+                check_expr(&l.end, errors);
+                check_block(&l.block, errors);
+            }
+            Statement::Return(r) => {
+                if let Some(e) = &r.val {
+                    check_expr(e, errors);
+                }
+            }
+            Statement::Break(_) | Statement::Continue(_) => (),
+            Statement::Block(b) => check_block(b, errors),
+        }
+    }
+}
+
+/// Check every int literal is in the 32-bit range
+///
+/// An <int literal> must be in the range -2147483648 to 2147483647,
+/// where the minimum is written as a unary minus applied to 2147483648.
+pub fn is_int_literal_in_range(p: &Program) -> Result<(), Vec<SemanticCheckError>> {
+    // array sizes are checked by `is_array_size_positive`
+    let mut errors = Vec::new();
+    for m in &p.method_decls {
+        check_block(&m.block, &mut errors);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_int_literal_value() {
+        assert_eq!(int_literal_value("2147483647", false), Some(i32::MAX));
+        assert_eq!(int_literal_value("2147483648", false), None);
+        assert_eq!(int_literal_value("2147483648", true), Some(i32::MIN));
+        assert_eq!(int_literal_value("2147483649", true), None);
+        assert_eq!(int_literal_value("0x7fffffff", false), Some(i32::MAX));
+        assert_eq!(int_literal_value("0x80000000", true), Some(i32::MIN));
+        assert_eq!(int_literal_value("0xffffffff", false), None);
+        assert_eq!(int_literal_value("0xA", false), Some(10));
+        assert_eq!(int_literal_value("99999999999999999999", false), None);
+    }
+}
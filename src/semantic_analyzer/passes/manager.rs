@@ -0,0 +1,338 @@
+//! semantic passes and the manager running them in dependency order
+
+use super::super::ir;
+use super::*;
+use crate::token;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Program representation a pass checks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// the token tree, before the IR is built
+    Tokens,
+    /// the IR, once it is built without errors
+    Ir,
+}
+
+/// A semantic check
+///
+/// Token-level passes run before the IR is built and implement
+/// `check_tokens`; IR-level passes run after it and implement `check_ir`.
+pub trait Pass {
+    /// unique name, used by `dependencies` and in timings
+    fn name(&self) -> &'static str;
+
+    fn stage(&self) -> Stage;
+
+    /// names of the passes that must run before this one
+    fn dependencies(&self) -> &[&'static str] {
+        &[]
+    }
+
+    fn check_tokens(&self, _p: &token::Program) -> Vec<SemanticCheckError> {
+        Vec::new()
+    }
+
+    fn check_ir(&self, _ir: &ir::IRRoot) -> Vec<SemanticCheckError> {
+        Vec::new()
+    }
+}
+
+/// Why the passes cannot be ordered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PassError {
+    DuplicatedPass(&'static str),
+    /// a pass depends on a pass that is not registered
+    UnknownDependency(&'static str, &'static str),
+    /// a token-level pass depends on an IR-level pass
+    StageMismatch(&'static str, &'static str),
+    /// the passes depending on each other, in no particular order
+    Cycle(Vec<&'static str>),
+}
+
+impl fmt::Display for PassError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DuplicatedPass(p) => write!(f, "pass `{}` is registered twice", p),
+            Self::UnknownDependency(p, d) => write!(f, "pass `{}` depends on unknown pass `{}`", p, d),
+            Self::StageMismatch(p, d) => {
+                write!(f, "token-level pass `{}` cannot depend on IR-level pass `{}`", p, d)
+            }
+            Self::Cycle(passes) => write!(f, "passes depend on each other: {}", passes.join(", ")),
+        }
+    }
+}
+
+impl std::error::Error for PassError {}
+
+/// Time taken by one run of a pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PassTiming {
+    pub name: &'static str,
+    pub duration: Duration,
+}
+
+/// Passes of the semantic analyzer
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+}
+
+impl Default for PassManager {
+    /// The checks of the decaf rules that are not done while building the IR
+    fn default() -> Self {
+        let mut m = Self::new();
+        m.register(HasMain);
+        m.register(ArraySizePositive);
+        m.register(MainVoid);
+        m.register(IntLiteralRange);
+        m
+    }
+}
+
+impl PassManager {
+    /// Manager without any pass
+    pub fn new() -> Self {
+        Self { passes: Vec::new() }
+    }
+
+    pub fn register<P: Pass + 'static>(&mut self, pass: P) {
+        self.passes.push(Box::new(pass));
+    }
+
+    /// Reorder the passes so each one follows its dependencies, keeping
+    /// the registration order otherwise
+    pub(crate) fn sort(&mut self) -> Result<(), PassError> {
+        for (i, p) in self.passes.iter().enumerate() {
+            if self.passes[..i].iter().any(|q| q.name() == p.name()) {
+                return Err(PassError::DuplicatedPass(p.name()));
+            }
+            for &d in p.dependencies() {
+                match self.passes.iter().find(|q| q.name() == d) {
+                    None => return Err(PassError::UnknownDependency(p.name(), d)),
+                    Some(q) if p.stage() == Stage::Tokens && q.stage() == Stage::Ir => {
+                        return Err(PassError::StageMismatch(p.name(), d))
+                    }
+                    Some(_) => (),
+                }
+            }
+        }
+
+        let mut todo = std::mem::take(&mut self.passes);
+        while !todo.is_empty() {
+            let done = &self.passes;
+            let ready = todo
+                .iter()
+                .position(|p| p.dependencies().iter().all(|d| done.iter().any(|q| q.name() == *d)));
+            match ready {
+                Some(i) => self.passes.push(todo.remove(i)),
+                None => return Err(PassError::Cycle(todo.iter().map(|p| p.name()).collect())),
+            }
+        }
+        Ok(())
+    }
+
+    fn run_stage<F>(&self, stage: Stage, check: F) -> (Vec<SemanticCheckError>, Vec<PassTiming>)
+    where
+        F: Fn(&dyn Pass) -> Vec<SemanticCheckError>,
+    {
+        let mut errors = Vec::new();
+        let mut timings = Vec::new();
+        for pass in self.passes.iter().filter(|pass| pass.stage() == stage) {
+            let start = Instant::now();
+            errors.extend(check(pass.as_ref()));
+            timings.push(PassTiming {
+                name: pass.name(),
+                duration: start.elapsed(),
+            });
+        }
+        (errors, timings)
+    }
+
+    /// Run the token-level passes in order
+    pub(crate) fn run_tokens(&self, p: &token::Program) -> (Vec<SemanticCheckError>, Vec<PassTiming>) {
+        self.run_stage(Stage::Tokens, |pass| pass.check_tokens(p))
+    }
+
+    /// Run the IR-level passes in order
+    pub(crate) fn run_ir(&self, ir: &ir::IRRoot) -> (Vec<SemanticCheckError>, Vec<PassTiming>) {
+        self.run_stage(Stage::Ir, |pass| pass.check_ir(ir))
+    }
+}
+
+/// pass 3
+struct HasMain;
+
+impl Pass for HasMain {
+    fn name(&self) -> &'static str {
+        "has-main"
+    }
+    fn stage(&self) -> Stage {
+        Stage::Tokens
+    }
+    fn check_tokens(&self, p: &token::Program) -> Vec<SemanticCheckError> {
+        has_main(p).err().into_iter().collect()
+    }
+}
+
+/// pass 4
+struct ArraySizePositive;
+
+impl Pass for ArraySizePositive {
+    fn name(&self) -> &'static str {
+        "array-size-positive"
+    }
+    fn stage(&self) -> Stage {
+        Stage::Tokens
+    }
+    fn check_tokens(&self, p: &token::Program) -> Vec<SemanticCheckError> {
+        is_array_size_positive(p).err().unwrap_or_default()
+    }
+}
+
+struct MainVoid;
+
+impl Pass for MainVoid {
+    fn name(&self) -> &'static str {
+        "main-void"
+    }
+    fn stage(&self) -> Stage {
+        Stage::Tokens
+    }
+    fn check_tokens(&self, p: &token::Program) -> Vec<SemanticCheckError> {
+        is_main_void(p).err().into_iter().collect()
+    }
+}
+
+struct IntLiteralRange;
+
+impl Pass for IntLiteralRange {
+    fn name(&self) -> &'static str {
+        "int-literal-range"
+    }
+    fn stage(&self) -> Stage {
+        Stage::Tokens
+    }
+    fn check_tokens(&self, p: &token::Program) -> Vec<SemanticCheckError> {
+        is_int_literal_in_range(p).err().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::semantic_analyzer::SemanticAnalyzer;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// pass recording when it runs
+    struct Probe {
+        name: &'static str,
+        stage: Stage,
+        dependencies: Vec<&'static str>,
+        log: Rc<RefCell<Vec<&'static str>>>,
+    }
+
+    impl Probe {
+        fn new(name: &'static str, stage: Stage, dependencies: &[&'static str], log: &Rc<RefCell<Vec<&'static str>>>) -> Self {
+            Self {
+                name,
+                stage,
+                dependencies: dependencies.to_vec(),
+                log: log.clone(),
+            }
+        }
+    }
+
+    impl Pass for Probe {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+        fn stage(&self) -> Stage {
+            self.stage
+        }
+        fn dependencies(&self) -> &[&'static str] {
+            &self.dependencies
+        }
+        fn check_tokens(&self, _p: &token::Program) -> Vec<SemanticCheckError> {
+            self.log.borrow_mut().push(self.name);
+            Vec::new()
+        }
+        fn check_ir(&self, ir: &ir::IRRoot) -> Vec<SemanticCheckError> {
+            self.log.borrow_mut().push(self.name);
+            // custom check: methods must not be called `foo`
+            ir.method_decls()
+                .filter(|(_, m)| m.name == "foo")
+                .map(|(_, m)| SemanticCheckError::Custom {
+                    pass: self.name,
+                    message: String::from("method named `foo`"),
+                    span: m.span,
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_pass_order() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut passes = PassManager::default();
+        passes.register(Probe::new("c", Stage::Ir, &["a", "has-main"], &log));
+        passes.register(Probe::new("b", Stage::Tokens, &["a"], &log));
+        passes.register(Probe::new("a", Stage::Tokens, &[], &log));
+        let analyzer = SemanticAnalyzer::with_passes(passes).unwrap();
+
+        let errors = analyzer
+            .create_ir(parse("class Program { void foo() {} void main() {} }").unwrap())
+            .unwrap_err();
+        assert!(matches!(errors[..], [SemanticCheckError::Custom { pass: "c", .. }]));
+        assert_eq!(*log.borrow(), vec!["a", "b", "c"]);
+        let timings: Vec<&str> = analyzer.timings().iter().map(|t| t.name).collect();
+        assert_eq!(
+            timings,
+            vec!["has-main", "array-size-positive", "main-void", "int-literal-range", "a", "b", "c"]
+        );
+
+        // the analyzer can be reused; IR-level passes only see well-formed IR
+        log.borrow_mut().clear();
+        assert!(analyzer.create_ir(parse("class Program { void main() {} }").unwrap()).is_ok());
+        assert!(analyzer.create_ir(parse("class Program { void main() { x = 1; } }").unwrap()).is_err());
+        assert_eq!(*log.borrow(), vec!["a", "b", "c", "a", "b"]);
+    }
+
+    #[test]
+    fn test_pass_order_errors() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let sort = |probes: Vec<Probe>| {
+            let mut passes = PassManager::new();
+            probes.into_iter().for_each(|p| passes.register(p));
+            passes.sort()
+        };
+        assert_eq!(
+            sort(vec![Probe::new("a", Stage::Tokens, &["b"], &log)]),
+            Err(PassError::UnknownDependency("a", "b"))
+        );
+        assert_eq!(
+            sort(vec![
+                Probe::new("a", Stage::Tokens, &["b"], &log),
+                Probe::new("b", Stage::Ir, &[], &log)
+            ]),
+            Err(PassError::StageMismatch("a", "b"))
+        );
+        assert_eq!(
+            sort(vec![
+                Probe::new("a", Stage::Ir, &["b"], &log),
+                Probe::new("b", Stage::Ir, &["a"], &log),
+                Probe::new("c", Stage::Ir, &[], &log)
+            ]),
+            Err(PassError::Cycle(vec!["a", "b"]))
+        );
+        assert_eq!(
+            sort(vec![
+                Probe::new("a", Stage::Ir, &[], &log),
+                Probe::new("a", Stage::Tokens, &[], &log)
+            ]),
+            Err(PassError::DuplicatedPass("a"))
+        );
+    }
+}
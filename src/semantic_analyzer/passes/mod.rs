@@ -1,22 +1,344 @@
+mod int_literal;
+mod manager;
 mod pre_ir_check;
+mod return_check;
+pub use int_literal::*;
+pub use manager::*;
 pub use pre_ir_check::*;
+pub use return_check::*;
 
+use super::ir::Type;
+use crate::span::Span;
+use std::error::Error;
+use std::fmt;
+
+/// Fix proposed for an unknown symbol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Suggestion {
+    /// a visible symbol with a similar name
+    Similar(String),
+    /// a method with a similar name, used as a variable
+    Method(String),
+    /// an external function, which is called with `callout`
+    Callout,
+}
+
+/// Violation of a semantic rule
+///
+/// `span` is where the error is; `decl` (or `prev`, `field`, `index`)
+/// is the declaration of the symbol involved.
 #[derive(Debug)]
 pub enum SemanticCheckError {
-    NonAsciiCharLiteral(char),
-    TypeMismatch(String),               // pass 12, 13, 14
-    DuplicatedSymbol(String),           // pass 1 
-    UnknownSymbol(String),              // pass 2, 9 
-    NoMainMethod,                       // pass 3
-    NonPositiveArraySize,               // pass 4
-    ExprCallNoReturn,                   // pass 6
-    ReturnTypeMismatch,                 // pass 7,8
-    ArrayLocationOnNonArrayVar,         // pass 10.a,
-    ArrayLocationOffsetTypeError,       // pass 10.b
-    ContinueOutOfForScope,
-    BreakOutOfForScope,
-    MethodArgumentNotMatch,
+    /// pass 1
+    DuplicatedSymbol { name: String, span: Span, prev: Span },
+    /// pass 2, 9
+    UnknownSymbol {
+        name: String,
+        span: Span,
+        suggestion: Option<Suggestion>,
+    },
+    /// pass 3
+    NoMainMethod { span: Span },
+    /// pass 4
+    NonPositiveArraySize { name: String, size: i32, span: Span },
+    /// pass 5
+    MethodArgumentCountMismatch {
+        method: String,
+        expected: usize,
+        found: usize,
+        span: Span,
+        decl: Span,
+    },
+    /// pass 5, `index` is 0-based
+    MethodArgumentTypeMismatch {
+        method: String,
+        index: usize,
+        expected: Type,
+        found: Type,
+        span: Span,
+        decl: Span,
+    },
+    /// pass 6
+    ExprCallNoReturn { method: String, span: Span, decl: Span },
+    /// pass 7
+    ReturnValueInVoidMethod { method: String, span: Span, decl: Span },
+    /// pass 8, `found` is void for a return without a value
+    ReturnTypeMismatch {
+        method: String,
+        expected: Type,
+        found: Type,
+        span: Span,
+        decl: Span,
+    },
+    /// pass 9
+    LocationNotVariable { name: String, span: Span, decl: Span },
+    /// pass 10.a
+    ArrayLocationOnNonArrayVar { name: String, span: Span, decl: Span },
+    /// pass 10.b
+    ArrayLocationOffsetTypeError { found: Type, span: Span },
+    /// pass 11
+    IfConditionNotBool { found: Type, span: Span },
+    /// pass 12
+    ArithOperandNotInt { found: Type, span: Span },
+    /// pass 13
+    EqOperandTypeMismatch { lhs: Type, rhs: Type, span: Span },
+    /// pass 14
+    CondOperandNotBool { found: Type, span: Span },
+    /// pass 15
+    AssignTypeMismatch {
+        name: String,
+        expected: Type,
+        found: Type,
+        span: Span,
+        decl: Span,
+    },
+    /// pass 16
+    CompoundAssignNotInt { found: Type, span: Span },
+    /// pass 17
+    ForBoundNotInt { found: Type, span: Span },
+    /// pass 18
+    BreakOutOfForScope { span: Span },
+    /// pass 18
+    ContinueOutOfForScope { span: Span },
+    NonAsciiCharLiteral { value: char, span: Span },
+    ArrayLocationWithoutOffset { name: String, span: Span, decl: Span },
+    IntLiteralOutOfRange { literal: String, span: Span },
+    ForIndexRedeclared { name: String, span: Span, index: Span },
+    MainNotVoid { found: Type, span: Span },
+    MethodNameIsField { name: String, span: Span, field: Span },
+    MethodCalledBeforeDeclaration { name: String, span: Span, decl: Span },
+    /// reported by a pass registered by the user of the analyzer
+    Custom {
+        pass: &'static str,
+        message: String,
+        span: Span,
+    },
+}
+
+impl SemanticCheckError {
+    /// Stable error code, one per variant: `E00nn` for rule `nn` of the
+    /// semantic rules, `E01nn` for the second error of a rule and for
+    /// checks that are not numbered in the spec, `E0200` for custom passes
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::DuplicatedSymbol { .. } => "E0001",
+            Self::UnknownSymbol { .. } => "E0002",
+            Self::NoMainMethod { .. } => "E0003",
+            Self::NonPositiveArraySize { .. } => "E0004",
+            Self::MethodArgumentCountMismatch { .. } => "E0005",
+            Self::ExprCallNoReturn { .. } => "E0006",
+            Self::ReturnValueInVoidMethod { .. } => "E0007",
+            Self::ReturnTypeMismatch { .. } => "E0008",
+            Self::LocationNotVariable { .. } => "E0009",
+            Self::ArrayLocationOnNonArrayVar { .. } => "E0010",
+            Self::IfConditionNotBool { .. } => "E0011",
+            Self::ArithOperandNotInt { .. } => "E0012",
+            Self::EqOperandTypeMismatch { .. } => "E0013",
+            Self::CondOperandNotBool { .. } => "E0014",
+            Self::AssignTypeMismatch { .. } => "E0015",
+            Self::CompoundAssignNotInt { .. } => "E0016",
+            Self::ForBoundNotInt { .. } => "E0017",
+            Self::BreakOutOfForScope { .. } => "E0018",
+            Self::NonAsciiCharLiteral { .. } => "E0101",
+            Self::ArrayLocationWithoutOffset { .. } => "E0102",
+            Self::IntLiteralOutOfRange { .. } => "E0103",
+            Self::ForIndexRedeclared { .. } => "E0104",
+            Self::MainNotVoid { .. } => "E0105",
+            Self::MethodNameIsField { .. } => "E0106",
+            Self::MethodCalledBeforeDeclaration { .. } => "E0107",
+            Self::MethodArgumentTypeMismatch { .. } => "E0108",
+            Self::ArrayLocationOffsetTypeError { .. } => "E0109",
+            Self::ContinueOutOfForScope { .. } => "E0110",
+            Self::Custom { .. } => "E0200",
+        }
+    }
+
+    /// Source range of the offending construct
+    pub fn span(&self) -> Span {
+        match self {
+            Self::DuplicatedSymbol { span, .. }
+            | Self::UnknownSymbol { span, .. }
+            | Self::NoMainMethod { span }
+            | Self::NonPositiveArraySize { span, .. }
+            | Self::MethodArgumentCountMismatch { span, .. }
+            | Self::MethodArgumentTypeMismatch { span, .. }
+            | Self::ExprCallNoReturn { span, .. }
+            | Self::ReturnValueInVoidMethod { span, .. }
+            | Self::ReturnTypeMismatch { span, .. }
+            | Self::LocationNotVariable { span, .. }
+            | Self::ArrayLocationOnNonArrayVar { span, .. }
+            | Self::ArrayLocationOffsetTypeError { span, .. }
+            | Self::IfConditionNotBool { span, .. }
+            | Self::ArithOperandNotInt { span, .. }
+            | Self::EqOperandTypeMismatch { span, .. }
+            | Self::CondOperandNotBool { span, .. }
+            | Self::AssignTypeMismatch { span, .. }
+            | Self::CompoundAssignNotInt { span, .. }
+            | Self::ForBoundNotInt { span, .. }
+            | Self::BreakOutOfForScope { span }
+            | Self::ContinueOutOfForScope { span }
+            | Self::NonAsciiCharLiteral { span, .. }
+            | Self::ArrayLocationWithoutOffset { span, .. }
+            | Self::IntLiteralOutOfRange { span, .. }
+            | Self::ForIndexRedeclared { span, .. }
+            | Self::MainNotVoid { span, .. }
+            | Self::MethodNameIsField { span, .. }
+            | Self::MethodCalledBeforeDeclaration { span, .. }
+            | Self::Custom { span, .. } => *span,
+        }
+    }
+
+    /// Declaration related to the error, with a description of it
+    pub fn related(&self) -> Option<(Span, String)> {
+        match self {
+            Self::DuplicatedSymbol { name, prev, .. } => Some((*prev, format!("`{}` first declared here", name))),
+            Self::MethodArgumentCountMismatch { method, decl, .. }
+            | Self::MethodArgumentTypeMismatch { method, decl, .. }
+            | Self::ExprCallNoReturn { method, decl, .. }
+            | Self::ReturnValueInVoidMethod { method, decl, .. }
+            | Self::ReturnTypeMismatch { method, decl, .. } => {
+                Some((*decl, format!("method `{}` declared here", method)))
+            }
+            Self::LocationNotVariable { name, decl, .. }
+            | Self::MethodCalledBeforeDeclaration { name, decl, .. } => {
+                Some((*decl, format!("method `{}` declared here", name)))
+            }
+            Self::ArrayLocationOnNonArrayVar { name, decl, .. }
+            | Self::ArrayLocationWithoutOffset { name, decl, .. }
+            | Self::AssignTypeMismatch { name, decl, .. } => Some((*decl, format!("`{}` declared here", name))),
+            Self::ForIndexRedeclared { name, index, .. } => {
+                Some((*index, format!("loop index `{}` declared here", name)))
+            }
+            Self::MethodNameIsField { name, field, .. } => Some((*field, format!("field `{}` declared here", name))),
+            _ => None,
+        }
+    }
+
+    /// How the error might be fixed
+    pub fn help(&self) -> Option<String> {
+        match self {
+            Self::UnknownSymbol {
+                suggestion: Some(Suggestion::Similar(s)),
+                ..
+            } => Some(format!("did you mean `{}`?", s)),
+            Self::UnknownSymbol {
+                suggestion: Some(Suggestion::Method(s)),
+                ..
+            } => Some(format!("did you mean to call the method `{}(...)`?", s)),
+            Self::UnknownSymbol {
+                name,
+                suggestion: Some(Suggestion::Callout),
+                ..
+            } => Some(format!("external functions are called with `callout(\"{}\", ...)`", name)),
+            _ => None,
+        }
+    }
+
+    /// The decaf rule violated by the error
+    pub fn rule(&self) -> &'static str {
+        match self {
+            Self::DuplicatedSymbol { .. } => "no identifier is declared twice in the same scope",
+            Self::UnknownSymbol { .. } => "no identifier is used before it is declared",
+            Self::NoMainMethod { .. } => "the program contains a definition for a method called main that has no parameters",
+            Self::NonPositiveArraySize { .. } => "the int literal in an array declaration must be greater than 0",
+            Self::MethodArgumentCountMismatch { .. } | Self::MethodArgumentTypeMismatch { .. } => "the number and types of arguments in a method call must be the same as the number and types of the formals",
+            Self::ExprCallNoReturn { .. } => "if a method call is used as an expression, the method must return a result",
+            Self::ReturnValueInVoidMethod { .. } => "a return statement must not have a return value unless it appears in the body of a method that is declared to return a value",
+            Self::ReturnTypeMismatch { .. } => "the expression in a return statement must have the same type as the declared result type of the enclosing method definition",
+            Self::LocationNotVariable { .. } => "an <id> used as a <location> must name a declared local/global variable or formal parameter",
+            Self::ArrayLocationOnNonArrayVar { .. } => "for all locations of the form <id>[<expr>], <id> must be an array variable",
+            Self::ArrayLocationOffsetTypeError { .. } => "for all locations of the form <id>[<expr>], the type of <expr> must be int",
+            Self::IfConditionNotBool { .. } => "the <expr> in an if statement must have type boolean",
+            Self::ArithOperandNotInt { .. } => "the operands of <arith op>s and <rel op>s must have type int",
+            Self::EqOperandTypeMismatch { .. } => "the operands of <eq op>s must have the same type, either int or boolean",
+            Self::CondOperandNotBool { .. } => "the operands of <cond op>s and the operand of logical not (!) must have type boolean",
+            Self::AssignTypeMismatch { .. } => "the <location> and the <expr> in an assignment must have the same type",
+            Self::CompoundAssignNotInt { .. } => "the <location> and the <expr> in an incrementing/decrementing assignment must be of type int",
+            Self::ForBoundNotInt { .. } => "the initial <expr> and the ending <expr> of for must have type int",
+            Self::BreakOutOfForScope { .. } | Self::ContinueOutOfForScope { .. } => "all break and continue statements must be contained within the body of a for",
+            Self::NonAsciiCharLiteral { .. } => "a char literal must be a printable ASCII character",
+            Self::ArrayLocationWithoutOffset { .. } => "an array variable can only be used as a location with an index",
+            Self::IntLiteralOutOfRange { .. } => "an int literal must be in the range -2147483648 to 2147483647",
+            Self::ForIndexRedeclared { .. } => "the index of a for loop must not be redeclared in the body of the loop",
+            Self::MainNotVoid { .. } => "the method main must be declared with the void return type",
+            Self::MethodNameIsField { .. } => "methods and fields share one global scope, so a method must not have the name of a field",
+            Self::MethodCalledBeforeDeclaration { .. } => "a method can only be called after its declaration, or recursively from its own body",
+            Self::Custom { .. } => "the program must pass every custom check",
+        }
+    }
 }
 
-pub type IRResult<T> = Result<T, Vec<SemanticCheckError>>;
+impl fmt::Display for SemanticCheckError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DuplicatedSymbol { name, .. } => write!(f, "`{}` is declared twice in the same scope", name),
+            Self::UnknownSymbol { name, .. } => write!(f, "cannot find `{}` in this scope", name),
+            Self::NoMainMethod { .. } => write!(f, "`main` method not found"),
+            Self::NonPositiveArraySize { name, size, .. } => {
+                write!(f, "array `{}` has size {}, which is not greater than 0", name, size)
+            }
+            Self::MethodArgumentCountMismatch {
+                method, expected, found, ..
+            } => write!(f, "method `{}` takes {} arguments but {} were supplied", method, expected, found),
+            Self::MethodArgumentTypeMismatch {
+                method,
+                index,
+                expected,
+                found,
+                ..
+            } => write!(
+                f,
+                "argument {} of `{}` has type {}, expected {}",
+                index + 1,
+                method,
+                found,
+                expected
+            ),
+            Self::ExprCallNoReturn { method, .. } => {
+                write!(f, "void method `{}` is called as an expression", method)
+            }
+            Self::ReturnValueInVoidMethod { method, .. } => {
+                write!(f, "return value in void method `{}`", method)
+            }
+            Self::ReturnTypeMismatch {
+                method, expected, found, ..
+            } => write!(f, "method `{}` returns {}, but the return statement has {}", method, expected, found),
+            Self::LocationNotVariable { name, .. } => write!(f, "`{}` is a method, not a variable", name),
+            Self::ArrayLocationOnNonArrayVar { name, .. } => write!(f, "cannot index non-array variable `{}`", name),
+            Self::ArrayLocationOffsetTypeError { found, .. } => write!(f, "array index has type {}, expected int", found),
+            Self::IfConditionNotBool { found, .. } => write!(f, "if condition has type {}, expected boolean", found),
+            Self::ArithOperandNotInt { found, .. } => {
+                write!(f, "operand of an arithmetic or relational operator has type {}, expected int", found)
+            }
+            Self::EqOperandTypeMismatch { lhs, rhs, .. } => {
+                write!(f, "operands of an equality operator have different types {} and {}", lhs, rhs)
+            }
+            Self::CondOperandNotBool { found, .. } => {
+                write!(f, "operand of a conditional operator has type {}, expected boolean", found)
+            }
+            Self::AssignTypeMismatch {
+                name, expected, found, ..
+            } => write!(f, "cannot assign {} to `{}` of type {}", found, name, expected),
+            Self::CompoundAssignNotInt { found, .. } => {
+                write!(f, "compound assignment operand has type {}, expected int", found)
+            }
+            Self::ForBoundNotInt { found, .. } => write!(f, "for loop bound has type {}, expected int", found),
+            Self::BreakOutOfForScope { .. } => write!(f, "`break` outside of a for loop"),
+            Self::ContinueOutOfForScope { .. } => write!(f, "`continue` outside of a for loop"),
+            Self::NonAsciiCharLiteral { value, .. } => write!(f, "non-ASCII character literal `{}`", value),
+            Self::ArrayLocationWithoutOffset { name, .. } => write!(f, "array `{}` used without an index", name),
+            Self::IntLiteralOutOfRange { literal, .. } => write!(f, "int literal `{}` is out of range", literal),
+            Self::ForIndexRedeclared { name, .. } => {
+                write!(f, "for loop index `{}` is redeclared in the loop body", name)
+            }
+            Self::MainNotVoid { found, .. } => write!(f, "`main` must return void, not {}", found),
+            Self::MethodNameIsField { name, .. } => write!(f, "method `{}` has the same name as a field", name),
+            Self::MethodCalledBeforeDeclaration { name, .. } => {
+                write!(f, "method `{}` is called before its declaration", name)
+            }
+            Self::Custom { pass, message, .. } => write!(f, "{} ({})", message, pass),
+        }
+    }
+}
 
+impl Error for SemanticCheckError {}
@@ -1,6 +1,7 @@
 //! checks before IR construction
 
-use super::SemanticCheckError;
+use super::super::ir;
+use super::{int_literal_value, SemanticCheckError};
 use crate::token::*;
 
 
@@ -14,33 +15,61 @@ pub fn has_main(p: &Program) -> Result<(), SemanticCheckError> {
     let has_main = p
         .method_decls
         .iter()
-        .filter(|&m| m.name == "main" && m.args.is_empty())
+        .filter(|&m| m.name.name == "main" && m.args.is_empty())
         .count()
         == 1;
     if has_main {
         Ok(())
     } else {
-        Err(SemanticCheckError::NoMainMethod)
+        Err(SemanticCheckError::NoMainMethod { span: p.span })
+    }
+}
+
+/// Check main returns void
+///
+/// Execution starts at method main, so its result would be lost.
+pub fn is_main_void(p: &Program) -> Result<(), SemanticCheckError> {
+    let invalid = p
+        .method_decls
+        .iter()
+        .find(|m| m.name.name == "main" && !matches!(m.return_type, Type::Void));
+    match invalid {
+        Some(m) => Err(SemanticCheckError::MainNotVoid {
+            found: ir::Type::from(&m.return_type),
+            span: m.name.span,
+        }),
+        None => Ok(()),
     }
 }
 
 /// Check all declared array size is positive (pass 4)
 ///
 /// The <int literal> in an array declaration must be greater than 0.
-pub fn is_array_size_positive(p: &Program) -> Result<(), SemanticCheckError> {
-    let is_valid = p
-        .field_decls
-        .iter()
-        .map(|d| {
-            d.loc
-                .iter()
-                .filter(|a| a.arr_size.is_some())
-                .map(|a| a.arr_size.unwrap() > 0)
-                .all(|b|b)
-        }).all(|b|b);
-    if is_valid {
+/// A size that does not fit in an int is reported here as well, since
+/// the IR cannot hold it.
+pub fn is_array_size_positive(p: &Program) -> Result<(), Vec<SemanticCheckError>> {
+    let mut errors = Vec::new();
+    for a in p.field_decls.iter().flat_map(|d| d.loc.iter()) {
+        let s = match a.arr_size.as_deref() {
+            Some(s) => s,
+            None => continue,
+        };
+        match int_literal_value(s, false) {
+            Some(size) if size <= 0 => errors.push(SemanticCheckError::NonPositiveArraySize {
+                name: a.name.name.clone(),
+                size,
+                span: a.span,
+            }),
+            Some(_) => (),
+            None => errors.push(SemanticCheckError::IntLiteralOutOfRange {
+                literal: s.to_string(),
+                span: a.span,
+            }),
+        }
+    }
+    if errors.is_empty() {
         Ok(())
     } else {
-        Err(SemanticCheckError::NonPositiveArraySize)
+        Err(errors)
     }
 }
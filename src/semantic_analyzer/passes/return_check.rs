@@ -0,0 +1,27 @@
+//! methods that can fall off their end without returning
+
+use super::super::ir;
+
+fn returns(s: &ir::Statement, ir: &ir::IRRoot) -> bool {
+    match s {
+        ir::Statement::Return(_) => true,
+        ir::Statement::Block(b) => always_returns(b, ir),
+        ir::Statement::IfElse(i) => {
+            always_returns(&i.true_block, ir) && i.false_block.as_ref().is_some_and(|b| always_returns(b, ir))
+        }
+        // a loop may not run at all, and `break` or `continue` stay in it
+        ir::Statement::For(_)
+        | ir::Statement::Break(_)
+        | ir::Statement::Continue(_)
+        | ir::Statement::Assign(_)
+        | ir::Statement::Call(_) => false,
+    }
+}
+
+/// Check every path through `b` ends in a return statement
+///
+/// Falling off the end of a method declared to return a result is a
+/// runtime error, so such methods need a trap at their end.
+pub fn always_returns(b: &ir::Block, ir: &ir::IRRoot) -> bool {
+    b.statements.iter().any(|&s| returns(ir.stmt(s), ir))
+}
@@ -0,0 +1,286 @@
+//! textual dump of the IR
+//!
+//! Every declaration gets a number, in source order, printed after its
+//! name as `x#3`. Uses of a declaration print the number instead of the
+//! declaration, and `return`, `break` and `continue` refer to their
+//! method or loop index the same way. Every expression is followed by
+//! its type.
+
+use super::ir;
+use std::collections::HashMap;
+
+const INDENT: &str = "    ";
+
+pub(crate) fn binary_op(op: ir::BinaryOp) -> &'static str {
+    match op {
+        ir::BinaryOp::Or => "||",
+        ir::BinaryOp::And => "&&",
+        ir::BinaryOp::EQ => "==",
+        ir::BinaryOp::NE => "!=",
+        ir::BinaryOp::GT => ">",
+        ir::BinaryOp::LT => "<",
+        ir::BinaryOp::GE => ">=",
+        ir::BinaryOp::LE => "<=",
+        ir::BinaryOp::Add => "+",
+        ir::BinaryOp::Sub => "-",
+        ir::BinaryOp::Mul => "*",
+        ir::BinaryOp::Div => "/",
+        ir::BinaryOp::Mod => "%",
+    }
+}
+
+pub(crate) fn assign_op(op: ir::AssignOp) -> &'static str {
+    match op {
+        ir::AssignOp::Assign => "=",
+        ir::AssignOp::AddAssign => "+=",
+        ir::AssignOp::SubAssign => "-=",
+        ir::AssignOp::MulAssign => "*=",
+        ir::AssignOp::DivAssign => "/=",
+    }
+}
+
+/// Declaration numbered by the printer
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Decl {
+    Var(ir::DeclId),
+    Method(ir::MethodId),
+}
+
+struct Printer<'a> {
+    ir: &'a ir::IRRoot,
+    /// number of each declaration
+    ids: HashMap<Decl, usize>,
+    out: String,
+    depth: usize,
+}
+
+impl Printer<'_> {
+    fn line(&mut self, s: &str) {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(s);
+        self.out.push('\n');
+    }
+
+    /// `name#id`, numbering the declaration `d` on its first use
+    fn name(&mut self, d: Decl, name: &str) -> String {
+        let next = self.ids.len();
+        let id = *self.ids.entry(d).or_insert(next);
+        format!("{}#{}", name, id)
+    }
+
+    fn var(&mut self, d: ir::DeclId) -> String {
+        self.name(Decl::Var(d), &self.ir.decl(d).name)
+    }
+
+    fn method(&mut self, m: ir::MethodId) -> String {
+        self.name(Decl::Method(m), &self.ir.method(m).name)
+    }
+
+    /// `int x#3` or `int a#0[10]`
+    fn var_decl(&mut self, id: ir::DeclId) -> String {
+        let name = self.var(id);
+        let d = self.ir.decl(id);
+        match d.arr_size {
+            Some(n) => format!("{} {}[{}]", d.type_, name, n),
+            None => format!("{} {}", d.type_, name),
+        }
+    }
+
+    fn location(&mut self, l: &ir::Location) -> String {
+        let name = self.var(l.decl);
+        match l.arr_size {
+            Some(i) => format!("{}[{}]", name, self.expr(i)),
+            None => name,
+        }
+    }
+
+    fn call(&mut self, c: &ir::Call) -> String {
+        match c {
+            ir::Call::Method(m) => {
+                let name = self.method(m.decl);
+                let args: Vec<String> = m.args.iter().map(|&a| self.expr(a)).collect();
+                format!("{}({})", name, args.join(", "))
+            }
+            ir::Call::Callout(c) => {
+                let mut args = vec![format!("{:?}", c.name)];
+                args.extend(c.args.iter().map(|a| match a {
+                    ir::CalloutArg::Expr(e) => self.expr(*e),
+                    ir::CalloutArg::StringLiteral(s, _) => format!("{:?}", s),
+                }));
+                format!("callout({})", args.join(", "))
+            }
+        }
+    }
+
+    /// `e: type`, with compound expressions in parentheses
+    fn expr(&mut self, e: ir::ExprId) -> String {
+        let e = self.ir.expr(e);
+        let s = match &e.expr {
+            ir::ExprType::Location(l) => self.location(l),
+            ir::ExprType::Literal(ir::Literal::Int(n)) => n.to_string(),
+            ir::ExprType::Literal(ir::Literal::Boolean(b)) => b.to_string(),
+            ir::ExprType::Call(c) => self.call(c),
+            ir::ExprType::Unary(u) => {
+                let op = match u.op {
+                    ir::UnaryOp::NegInt => "-",
+                    ir::UnaryOp::NegBool => "!",
+                };
+                format!("{}({})", op, self.expr(u.expr))
+            }
+            ir::ExprType::Binary(b) => {
+                format!("({} {} {})", self.expr(b.lhs), binary_op(b.op), self.expr(b.rhs))
+            }
+            ir::ExprType::Error => String::from("<error>"),
+        };
+        format!("{}: {}", s, e.type_)
+    }
+
+    fn block(&mut self, b: &ir::Block) {
+        self.depth += 1;
+        for &d in &b.var_decls {
+            let d = self.var_decl(d);
+            self.line(&d);
+        }
+        for &s in &b.statements {
+            self.statement(s);
+        }
+        self.depth -= 1;
+    }
+
+    /// `break` and `continue` refer to the index of their loop
+    fn loop_index(&mut self, for_: ir::StmtId) -> String {
+        match self.ir.stmt(for_) {
+            ir::Statement::For(l) => self.var(l.index_decl),
+            _ => String::from("?"),
+        }
+    }
+
+    fn statement(&mut self, s: ir::StmtId) {
+        match self.ir.stmt(s) {
+            ir::Statement::Assign(a) => {
+                let line = format!("{} {} {}", self.location(&a.dst), assign_op(a.op), self.expr(a.val));
+                self.line(&line);
+            }
+            ir::Statement::Call(c) => {
+                let line = self.call(c);
+                self.line(&line);
+            }
+            ir::Statement::IfElse(i) => {
+                let line = format!("if {} {{", self.expr(i.cond));
+                self.line(&line);
+                self.block(&i.true_block);
+                if let Some(b) = &i.false_block {
+                    self.line("} else {");
+                    self.block(b);
+                }
+                self.line("}");
+            }
+            ir::Statement::For(l) => {
+                let index = self.var(l.index_decl);
+                let line = format!("for {} = {}, {} {{", index, self.expr(l.start), self.expr(l.end));
+                self.line(&line);
+                self.block(&l.block);
+                self.line("}");
+            }
+            ir::Statement::Return(r) => {
+                let method = self.method(r.func);
+                let line = match r.val {
+                    Some(v) => format!("return[{}] {}", method, self.expr(v)),
+                    None => format!("return[{}]", method),
+                };
+                self.line(&line);
+            }
+            ir::Statement::Break(b) => {
+                let line = format!("break[{}]", self.loop_index(b.for_));
+                self.line(&line);
+            }
+            ir::Statement::Continue(c) => {
+                let line = format!("continue[{}]", self.loop_index(c.for_));
+                self.line(&line);
+            }
+            ir::Statement::Block(b) => {
+                self.line("{");
+                self.block(b);
+                self.line("}");
+            }
+        }
+    }
+
+    fn method_decl(&mut self, id: ir::MethodId) {
+        let name = self.method(id);
+        let m = self.ir.method(id);
+        let args: Vec<String> = m.args.iter().map(|&a| self.var_decl(a)).collect();
+        let trap = if m.needs_fall_off_trap { " [may fall off]" } else { "" };
+        self.line(&format!("{} {}({}){} {{", m.return_type, name, args.join(", "), trap));
+        self.block(&m.block);
+        self.line("}");
+    }
+}
+
+/// Compact text of `root`, printed by `-target inter`
+pub fn print_ir(root: &ir::IRRoot) -> String {
+    let mut printer = Printer {
+        ir: root,
+        ids: HashMap::new(),
+        out: String::new(),
+        depth: 0,
+    };
+    for &d in &root.root.field_decls {
+        let d = printer.var_decl(d);
+        printer.line(&d);
+    }
+    for &m in &root.root.method_decls {
+        if !printer.out.is_empty() {
+            printer.out.push('\n');
+        }
+        printer.method_decl(m);
+    }
+    printer.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::check;
+
+    fn print(src: &str) -> String {
+        print_ir(&check(src))
+    }
+
+    #[test]
+    fn test_print_ir() {
+        let src = "class Program { int a[10]; \
+                   int fact(int n) { if (n <= 1) { return 1; } return n * fact(n - 1); } \
+                   void main() { for i = 0, 10 { a[i] += -fact(i); if (!true) { break; } } \
+                   callout(\"printf\", \"%d\\n\", a[9]); } }";
+        let expected = "\
+int a#0[10]
+
+int fact#1(int n#2) {
+    if (n#2: int <= 1: int): boolean {
+        return[fact#1] 1: int
+    }
+    return[fact#1] (n#2: int * fact#1((n#2: int - 1: int): int): int): int
+}
+
+void main#3() {
+    for i#4 = 0: int, 10: int {
+        a#0[i#4: int] += -(fact#1(i#4: int): int): int
+        if !(true: boolean): boolean {
+            break[i#4]
+        }
+    }
+    callout(\"printf\", \"%d\\n\", a#0[9: int]: int)
+}
+";
+        assert_eq!(print(src), expected);
+    }
+
+    #[test]
+    fn test_fall_off() {
+        let out = print("class Program { int f() { if (true) { return 1; } } void main() { f(); } }");
+        assert_eq!(out.lines().next(), Some("int f#0() [may fall off] {"));
+    }
+}
@@ -0,0 +1,69 @@
+//! suggestions for misspelled names
+
+/// Levenshtein distance between `a` and `b`, in characters
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // distances from a prefix of `a` to every prefix of `b`
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitute = prev + usize::from(ca != cb);
+            prev = row[j + 1];
+            row[j + 1] = substitute.min(prev + 1).min(row[j] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The candidate closest to `name`, if it is close enough to be a typo
+///
+/// A candidate differing only in case always matches; otherwise up to one
+/// edit per three characters of `name` is allowed, so names shorter than
+/// three characters only match in case. Ties go to the alphabetically
+/// first candidate.
+pub fn similar_name<'a, I>(name: &str, candidates: I) -> Option<String>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let max = name.chars().count() / 3;
+    candidates
+        .into_iter()
+        .filter(|c| c.as_str() != name)
+        .filter_map(|c| {
+            let d = if c.eq_ignore_ascii_case(name) {
+                0
+            } else {
+                edit_distance(name, c)
+            };
+            (d <= max).then_some((d, c))
+        })
+        .min()
+        .map(|(_, c)| c.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("count", "cuont"), 2);
+    }
+
+    #[test]
+    fn test_similar_name() {
+        let names: Vec<String> = ["count", "counter", "Max", "x"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(similar_name("cont", &names).as_deref(), Some("count"));
+        assert_eq!(similar_name("MAX", &names).as_deref(), Some("Max"));
+        assert_eq!(similar_name("X", &names).as_deref(), Some("x"));
+        assert_eq!(similar_name("y", &names), None);
+        assert_eq!(similar_name("Mx", &names), None);
+        assert_eq!(similar_name("cout", &names).as_deref(), Some("count"));
+        assert_eq!(similar_name("total", &names), None);
+    }
+}
@@ -0,0 +1,304 @@
+//! declarations and uses of the symbols of a program
+
+use super::ir;
+use crate::span::Span;
+use std::collections::HashMap;
+
+pub type SymbolId = usize;
+pub type ScopeId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Field,
+    Method,
+    Parameter,
+    Local,
+    LoopIndex,
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub id: SymbolId,
+    pub name: String,
+    pub kind: SymbolKind,
+    /// type of a variable, or return type of a method
+    pub type_: ir::Type,
+    pub arr_size: Option<i32>,
+    /// scope the symbol is declared in
+    pub scope: ScopeId,
+    /// span of the name in the declaration
+    pub span: Span,
+    /// span of the whole declaration
+    pub decl_span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    Global,
+    Method,
+    For,
+    Block,
+}
+
+#[derive(Debug, Clone)]
+pub struct Scope {
+    pub id: ScopeId,
+    pub kind: ScopeKind,
+    pub parent: Option<ScopeId>,
+    /// source the symbols of the scope are visible in; the body of a
+    /// `for`, since its bounds are outside of the loop
+    pub span: Span,
+}
+
+/// Name in a statement resolved to its declaration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Use {
+    pub symbol: SymbolId,
+    pub span: Span,
+}
+
+/// Every declaration and use of a program, built with the IR
+///
+/// Symbols and scopes are numbered in source order, so a scope is nested
+/// in the scopes with a smaller id containing it.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+    scopes: Vec<Scope>,
+    uses: Vec<Use>,
+}
+
+impl SymbolTable {
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    pub fn symbol(&self, id: SymbolId) -> &Symbol {
+        &self.symbols[id]
+    }
+
+    pub fn scopes(&self) -> &[Scope] {
+        &self.scopes
+    }
+
+    pub fn scope(&self, id: ScopeId) -> &Scope {
+        &self.scopes[id]
+    }
+
+    /// Uses in source order
+    pub fn uses(&self) -> &[Use] {
+        &self.uses
+    }
+
+    /// `scope` and the scopes enclosing it, innermost first
+    pub fn scope_chain(&self, scope: ScopeId) -> Vec<ScopeId> {
+        let mut chain = vec![scope];
+        while let Some(parent) = self.scopes[*chain.last().unwrap()].parent {
+            chain.push(parent);
+        }
+        chain
+    }
+
+    /// Innermost scope containing `pos`
+    pub fn scope_at(&self, pos: usize) -> Option<ScopeId> {
+        self.scopes.iter().rev().find(|s| s.span.contains(pos)).map(|s| s.id)
+    }
+
+    /// Declaration `name` refers to at `pos`
+    ///
+    /// Methods are only visible after their declaration starts, as in
+    /// the IR.
+    pub fn lookup(&self, name: &str, pos: usize) -> Option<&Symbol> {
+        let scope = self.scope_at(pos)?;
+        self.scope_chain(scope).into_iter().find_map(|scope| {
+            self.symbols
+                .iter()
+                .rev()
+                .filter(|s| s.scope == scope && s.name == name)
+                .find(|s| s.kind != SymbolKind::Method || s.decl_span.start <= pos)
+        })
+    }
+
+    /// Symbol declared or used at `pos`
+    pub fn symbol_at(&self, pos: usize) -> Option<&Symbol> {
+        match self.uses.iter().find(|u| u.span.contains(pos)) {
+            Some(u) => Some(self.symbol(u.symbol)),
+            None => self.symbols.iter().find(|s| s.span.contains(pos)),
+        }
+    }
+
+    /// Uses of the symbol `id`, in source order
+    pub fn uses_of(&self, id: SymbolId) -> impl Iterator<Item = &Use> {
+        self.uses.iter().filter(move |u| u.symbol == id)
+    }
+}
+
+/// Records the symbol table while the analyzer builds the IR
+#[derive(Default)]
+pub(super) struct SymbolTableBuilder {
+    table: SymbolTable,
+    /// scopes entered and not left yet
+    stack: Vec<ScopeId>,
+    vars: HashMap<ir::DeclId, SymbolId>,
+    methods: HashMap<ir::MethodId, SymbolId>,
+}
+
+impl SymbolTableBuilder {
+    pub fn table(&self) -> &SymbolTable {
+        &self.table
+    }
+
+    pub fn enter(&mut self, kind: ScopeKind, span: Span) {
+        let id = self.table.scopes.len();
+        self.table.scopes.push(Scope {
+            id,
+            kind,
+            parent: self.stack.last().copied(),
+            span,
+        });
+        self.stack.push(id);
+    }
+
+    pub fn exit(&mut self) {
+        self.stack.pop();
+    }
+
+    /// Add `symbol` to the current scope, returning its id
+    fn declare(&mut self, mut symbol: Symbol) -> SymbolId {
+        symbol.id = self.table.symbols.len();
+        symbol.scope = *self.stack.last().unwrap();
+        self.table.symbols.push(symbol);
+        self.table.symbols.len() - 1
+    }
+
+    pub fn declare_var(&mut self, id: ir::DeclId, d: &ir::VarDecl, kind: SymbolKind, span: Span) {
+        let symbol = Symbol {
+            id: 0,
+            name: d.name.clone(),
+            kind,
+            type_: d.type_,
+            arr_size: d.arr_size,
+            scope: 0,
+            span,
+            decl_span: d.span,
+        };
+        let symbol = self.declare(symbol);
+        self.vars.insert(id, symbol);
+    }
+
+    pub fn declare_method(&mut self, id: ir::MethodId, m: &ir::MethodDecl, span: Span) {
+        let symbol = Symbol {
+            id: 0,
+            name: m.name.clone(),
+            kind: SymbolKind::Method,
+            type_: m.return_type,
+            arr_size: None,
+            scope: 0,
+            span,
+            decl_span: m.span,
+        };
+        let symbol = self.declare(symbol);
+        self.methods.insert(id, symbol);
+    }
+
+    fn use_(&mut self, symbol: Option<SymbolId>, span: Span) {
+        if let Some(symbol) = symbol {
+            // a call is resolved after its arguments
+            let i = self.table.uses.partition_point(|u| u.span.start <= span.start);
+            self.table.uses.insert(i, Use { symbol, span });
+        }
+    }
+
+    pub fn use_var(&mut self, id: ir::DeclId, span: Span) {
+        self.use_(self.vars.get(&id).copied(), span);
+    }
+
+    pub fn use_method(&mut self, id: ir::MethodId, span: Span) {
+        self.use_(self.methods.get(&id).copied(), span);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::semantic_analyzer::SemanticAnalyzer;
+
+    fn symbol_table(src: &str) -> SymbolTable {
+        let analyzer = SemanticAnalyzer::new();
+        let _ = analyzer.create_ir(parse(src).unwrap());
+        analyzer.symbol_table()
+    }
+
+    /// position of the `n`th occurrence of `pat` in `src`
+    fn pos(src: &str, pat: &str, n: usize) -> usize {
+        src.match_indices(pat).nth(n).unwrap().0
+    }
+
+    const SRC: &str = "class Program { int a[10], n; int f(int x) { int y; y = x + n; return y; } \
+                       void main() { int n; n = f(a[0]); for i = 0, n { int a; a = i; } } }";
+
+    #[test]
+    fn test_declarations() {
+        let table = symbol_table(SRC);
+        let symbols: Vec<(&str, SymbolKind)> = table.symbols().iter().map(|s| (s.name.as_str(), s.kind)).collect();
+        assert_eq!(
+            symbols,
+            vec![
+                ("a", SymbolKind::Field),
+                ("n", SymbolKind::Field),
+                ("f", SymbolKind::Method),
+                ("x", SymbolKind::Parameter),
+                ("y", SymbolKind::Local),
+                ("main", SymbolKind::Method),
+                ("n", SymbolKind::Local),
+                ("i", SymbolKind::LoopIndex),
+                ("a", SymbolKind::Local),
+            ]
+        );
+        assert_eq!(table.symbol(0).arr_size, Some(10));
+        assert_eq!(table.symbol(2).type_, ir::Type::Int);
+        assert_eq!(&SRC[table.symbol(5).span.start..table.symbol(5).span.end], "main");
+
+        let chain: Vec<ScopeKind> = table
+            .scope_chain(table.symbol(8).scope)
+            .into_iter()
+            .map(|s| table.scope(s).kind)
+            .collect();
+        assert_eq!(chain, vec![ScopeKind::For, ScopeKind::Method, ScopeKind::Global]);
+    }
+
+    #[test]
+    fn test_lookup() {
+        let table = symbol_table(SRC);
+        let lookup = |name, pos| table.lookup(name, pos).map(|s| s.id);
+        assert_eq!(lookup("n", pos(SRC, "x + n", 0)), Some(1));
+        assert_eq!(lookup("n", pos(SRC, "n = f", 0)), Some(6));
+        assert_eq!(lookup("a", pos(SRC, "f(a[0])", 0)), Some(0));
+        assert_eq!(lookup("a", pos(SRC, "a = i", 0)), Some(8));
+        assert_eq!(lookup("i", pos(SRC, "n = f", 0)), None);
+        // methods are visible from their declaration on
+        assert_eq!(lookup("f", pos(SRC, "return y", 0)), Some(2));
+        assert_eq!(lookup("main", pos(SRC, "return y", 0)), None);
+    }
+
+    #[test]
+    fn test_uses() {
+        let table = symbol_table(SRC);
+        let uses: Vec<&str> = table.uses().iter().map(|u| &SRC[u.span.start..u.span.end]).collect();
+        assert_eq!(uses, vec!["y", "x", "n", "y", "n", "f", "a", "n", "a", "i"]);
+        assert_eq!(table.symbol_at(pos(SRC, "a[0]", 0)).map(|s| s.id), Some(0));
+        assert_eq!(table.symbol_at(pos(SRC, "int x", 0) + 4).map(|s| s.id), Some(3));
+        assert_eq!(table.symbol_at(pos(SRC, "for", 0)).map(|s| s.id), None);
+        let n: Vec<usize> = table.uses_of(6).map(|u| u.span.start).collect();
+        assert_eq!(n, vec![pos(SRC, "n = f", 0), pos(SRC, "0, n", 0) + 3]);
+    }
+
+    #[test]
+    fn test_illegal_program() {
+        let src = "class Program { void main() { int x; x = y + x; } }";
+        let table = symbol_table(src);
+        assert_eq!(table.symbols().len(), 2);
+        assert_eq!(table.uses_of(1).count(), 2);
+    }
+}
@@ -0,0 +1,430 @@
+//! control-flow graph of basic blocks of a lowered function
+//!
+//! Besides the blocks of the code, the graph has a virtual exit block,
+//! the successor of every block ending with a `return` or a fall-off
+//! trap, so post-dominators have a single root.
+
+use super::{Function, Instr, Label, Program};
+use std::collections::HashMap;
+
+pub type BlockId = usize;
+
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+    pub id: BlockId,
+    /// instructions, starting with the labels of the block
+    pub code: Vec<Instr>,
+    pub preds: Vec<BlockId>,
+    pub succs: Vec<BlockId>,
+}
+
+/// Loop whose header dominates every block of its body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loop {
+    pub header: BlockId,
+    /// sources of the back edges to the header
+    pub latches: Vec<BlockId>,
+    /// header and body, sorted
+    pub blocks: Vec<BlockId>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    blocks: Vec<BasicBlock>,
+    rpo: Vec<BlockId>,
+    idom: Vec<Option<BlockId>>,
+    ipdom: Vec<Option<BlockId>>,
+    frontiers: Vec<Vec<BlockId>>,
+    loops: Vec<Loop>,
+}
+
+impl Cfg {
+    /// Graph of `f`, with every analysis computed
+    pub fn new(f: &Function) -> Self {
+        let mut blocks = split(&f.code);
+        link(&mut blocks);
+        let succs: Vec<Vec<BlockId>> = blocks.iter().map(|b| b.succs.clone()).collect();
+        let preds: Vec<Vec<BlockId>> = blocks.iter().map(|b| b.preds.clone()).collect();
+        let exit = blocks.len() - 1;
+
+        let mut rpo = postorder(0, &succs);
+        rpo.reverse();
+        let idom = immediate_dominators(0, &succs, &preds);
+        let ipdom = immediate_dominators(exit, &preds, &succs);
+        let frontiers = frontiers(0, &preds, &idom);
+        let mut cfg = Self {
+            blocks,
+            rpo,
+            idom,
+            ipdom,
+            frontiers,
+            loops: Vec::new(),
+        };
+        cfg.loops = cfg.natural_loops();
+        cfg
+    }
+
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    pub fn block(&self, id: BlockId) -> &BasicBlock {
+        &self.blocks[id]
+    }
+
+    pub fn entry(&self) -> BlockId {
+        0
+    }
+
+    /// The virtual exit block, which has no code
+    pub fn exit(&self) -> BlockId {
+        self.blocks.len() - 1
+    }
+
+    pub fn preds(&self, id: BlockId) -> &[BlockId] {
+        &self.blocks[id].preds
+    }
+
+    pub fn succs(&self, id: BlockId) -> &[BlockId] {
+        &self.blocks[id].succs
+    }
+
+    /// Blocks reachable from the entry, in reverse postorder
+    pub fn reverse_postorder(&self) -> &[BlockId] {
+        &self.rpo
+    }
+
+    pub fn is_reachable(&self, id: BlockId) -> bool {
+        id == self.entry() || self.idom[id].is_some()
+    }
+
+    /// Immediate dominator; `None` for the entry and unreachable blocks
+    pub fn idom(&self, id: BlockId) -> Option<BlockId> {
+        self.idom[id]
+    }
+
+    /// Immediate post-dominator; `None` for the exit and blocks that
+    /// cannot reach it
+    pub fn ipdom(&self, id: BlockId) -> Option<BlockId> {
+        self.ipdom[id]
+    }
+
+    /// `a` dominates `b`, which must be reachable
+    pub fn dominates(&self, a: BlockId, b: BlockId) -> bool {
+        chain_contains(&self.idom, a, b)
+    }
+
+    /// `a` post-dominates `b`, which must reach the exit
+    pub fn post_dominates(&self, a: BlockId, b: BlockId) -> bool {
+        chain_contains(&self.ipdom, a, b)
+    }
+
+    /// Blocks where the dominance of `id` ends, sorted
+    pub fn dominance_frontier(&self, id: BlockId) -> &[BlockId] {
+        &self.frontiers[id]
+    }
+
+    /// Natural loops, one per header, sorted by header
+    pub fn loops(&self) -> &[Loop] {
+        &self.loops
+    }
+
+    fn natural_loops(&self) -> Vec<Loop> {
+        let mut loops: Vec<Loop> = Vec::new();
+        for &latch in &self.rpo {
+            for &header in self.succs(latch) {
+                if !self.dominates(header, latch) {
+                    continue;
+                }
+                let i = match loops.iter().position(|l| l.header == header) {
+                    Some(i) => i,
+                    None => {
+                        loops.push(Loop { header, latches: Vec::new(), blocks: vec![header] });
+                        loops.len() - 1
+                    }
+                };
+                let l = &mut loops[i];
+                l.latches.push(latch);
+                // blocks reaching the latch without going through the header
+                let mut todo = vec![latch];
+                while let Some(b) = todo.pop() {
+                    if !l.blocks.contains(&b) {
+                        l.blocks.push(b);
+                        todo.extend(self.preds(b).iter().filter(|&&p| self.is_reachable(p)));
+                    }
+                }
+            }
+        }
+        for l in &mut loops {
+            l.latches.sort_unstable();
+            l.blocks.sort_unstable();
+        }
+        loops.sort_by_key(|l| l.header);
+        loops
+    }
+
+    /// Text of the graph: every block with its edges and dominators,
+    /// then the loops
+    pub fn dump(&self, program: &Program) -> String {
+        let ids = |ids: &[BlockId]| ids.iter().map(|b| format!("B{}", b)).collect::<Vec<_>>().join(" ");
+        let opt = |b: Option<BlockId>| b.map_or(String::from("-"), |b| format!("B{}", b));
+        let mut out = String::new();
+        for b in &self.blocks {
+            let name = if b.id == self.exit() { format!("B{} (exit)", b.id) } else { format!("B{}", b.id) };
+            if !self.is_reachable(b.id) {
+                out.push_str(&format!("{}  unreachable\n", name));
+            } else {
+                out.push_str(&format!(
+                    "{}  preds: [{}]  succs: [{}]  idom: {}  ipdom: {}  frontier: [{}]\n",
+                    name,
+                    ids(&b.preds),
+                    ids(&b.succs),
+                    opt(self.idom(b.id)),
+                    opt(self.ipdom(b.id)),
+                    ids(self.dominance_frontier(b.id)),
+                ));
+            }
+            for i in &b.code {
+                out.push_str(&format!("    {}\n", program.instr(i)));
+            }
+        }
+        for l in &self.loops {
+            out.push_str(&format!(
+                "loop B{}  latches: [{}]  blocks: [{}]\n",
+                l.header,
+                ids(&l.latches),
+                ids(&l.blocks)
+            ));
+        }
+        out
+    }
+}
+
+/// Blocks of `code`, ending with an empty exit block; a block starts at
+/// a label or after a jump, and ends before a label or after a jump
+fn split(code: &[Instr]) -> Vec<BasicBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Vec<Instr> = Vec::new();
+    for i in code {
+        let starts_block = matches!(i, Instr::Label(_)) && current.iter().any(|i| !matches!(i, Instr::Label(_)));
+        if starts_block {
+            blocks.push(std::mem::take(&mut current));
+        }
+        current.push(i.clone());
+        if i.is_terminator() {
+            blocks.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() || blocks.is_empty() {
+        blocks.push(current);
+    }
+    blocks.push(Vec::new());
+    blocks
+        .into_iter()
+        .enumerate()
+        .map(|(id, code)| BasicBlock { id, code, preds: Vec::new(), succs: Vec::new() })
+        .collect()
+}
+
+/// Add the edges between `blocks`
+fn link(blocks: &mut [BasicBlock]) {
+    let exit = blocks.len() - 1;
+    let mut labels: HashMap<Label, BlockId> = HashMap::new();
+    for b in blocks.iter() {
+        for i in &b.code {
+            if let Instr::Label(l) = i {
+                labels.insert(*l, b.id);
+            }
+        }
+    }
+    for id in 0..exit {
+        let succs = match blocks[id].code.last() {
+            Some(Instr::Return(_)) | Some(Instr::FallOff) => vec![exit],
+            Some(i @ (Instr::Jump(_) | Instr::Branch { .. })) => i.targets().iter().map(|l| labels[l]).collect(),
+            // falls through, also from the end of the code to the exit
+            _ => vec![id + 1],
+        };
+        for s in succs {
+            if !blocks[id].succs.contains(&s) {
+                blocks[id].succs.push(s);
+                blocks[s].preds.push(id);
+            }
+        }
+    }
+}
+
+fn postorder(root: BlockId, succs: &[Vec<BlockId>]) -> Vec<BlockId> {
+    let mut visited = vec![false; succs.len()];
+    let mut order = Vec::new();
+    // blocks with the index of their next successor to visit
+    let mut stack = vec![(root, 0)];
+    visited[root] = true;
+    while let Some((b, i)) = stack.pop() {
+        match succs[b].get(i) {
+            Some(&s) => {
+                stack.push((b, i + 1));
+                if !visited[s] {
+                    visited[s] = true;
+                    stack.push((s, 0));
+                }
+            }
+            None => order.push(b),
+        }
+    }
+    order
+}
+
+/// Immediate dominators in the graph from `root`, following Cooper,
+/// Harvey and Kennedy, "A Simple, Fast Dominance Algorithm"
+fn immediate_dominators(root: BlockId, succs: &[Vec<BlockId>], preds: &[Vec<BlockId>]) -> Vec<Option<BlockId>> {
+    let order = postorder(root, succs);
+    let mut number = vec![usize::MAX; succs.len()];
+    for (i, &b) in order.iter().enumerate() {
+        number[b] = i;
+    }
+    let mut idom: Vec<Option<BlockId>> = vec![None; succs.len()];
+    idom[root] = Some(root);
+    let intersect = |idom: &[Option<BlockId>], mut a: BlockId, mut b: BlockId| {
+        while a != b {
+            while number[a] < number[b] {
+                a = idom[a].unwrap();
+            }
+            while number[b] < number[a] {
+                b = idom[b].unwrap();
+            }
+        }
+        a
+    };
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &b in order.iter().rev().filter(|&&b| b != root) {
+            let new = preds[b]
+                .iter()
+                .filter(|&&p| idom[p].is_some())
+                .fold(None, |new, &p| Some(new.map_or(p, |q| intersect(&idom, p, q))));
+            if new != idom[b] {
+                idom[b] = new;
+                changed = true;
+            }
+        }
+    }
+    idom[root] = None;
+    idom
+}
+
+/// Dominance frontier of every block, from its immediate dominators
+fn frontiers(root: BlockId, preds: &[Vec<BlockId>], idom: &[Option<BlockId>]) -> Vec<Vec<BlockId>> {
+    let mut df: Vec<Vec<BlockId>> = vec![Vec::new(); preds.len()];
+    let reachable = |b: BlockId| b == root || idom[b].is_some();
+    for b in (0..preds.len()).filter(|&b| reachable(b)) {
+        let preds: Vec<BlockId> = preds[b].iter().copied().filter(|&p| reachable(p)).collect();
+        if preds.len() < 2 {
+            continue;
+        }
+        for p in preds {
+            let mut runner = Some(p);
+            while let Some(r) = runner.filter(|&r| Some(r) != idom[b]) {
+                if !df[r].contains(&b) {
+                    df[r].push(b);
+                }
+                runner = idom[r];
+            }
+        }
+    }
+    df.iter_mut().for_each(|f| f.sort_unstable());
+    df
+}
+
+/// `a` is `b` or one of its ancestors in the tree `parent`
+fn chain_contains(parent: &[Option<BlockId>], a: BlockId, b: BlockId) -> bool {
+    let mut node = Some(b);
+    while let Some(n) = node {
+        if n == a {
+            return true;
+        }
+        node = parent[n];
+    }
+    false
+}
+
+/// Graphs of every function of `program`, one after the other
+pub fn dump(program: &Program) -> String {
+    program
+        .functions
+        .iter()
+        .map(|f| format!("{}:\n{}", f.name, Cfg::new(f).dump(program)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::lower;
+
+    const SRC: &str = "class Program { void main() { int x; \
+                       for i = 0, 10 { if (i == 5) { break; } x += i; } \
+                       callout(\"printf\", \"%d\", x); } }";
+
+    #[test]
+    fn test_blocks() {
+        let p = lower(SRC);
+        let cfg = Cfg::new(&p.functions[0]);
+        let expected = "\
+B0  preds: []  succs: [B1]  idom: -  ipdom: B1  frontier: []
+    x#0 = 0
+    i#1 = 0
+B1  preds: [B0 B5]  succs: [B2 B6]  idom: B0  ipdom: B6  frontier: [B1]
+    L0:
+    t0 = i#1 < 10
+    if t0 goto L1 else L3
+B2  preds: [B1]  succs: [B3 B4]  idom: B1  ipdom: B6  frontier: [B1 B6]
+    L1:
+    t1 = i#1 == 5
+    if t1 goto L4 else L5
+B3  preds: [B2]  succs: [B6]  idom: B2  ipdom: B6  frontier: [B6]
+    L4:
+    goto L3
+B4  preds: [B2]  succs: [B5]  idom: B2  ipdom: B5  frontier: [B1]
+    L5:
+    t2 = x#0 + i#1
+    x#0 = t2
+B5  preds: [B4]  succs: [B1]  idom: B4  ipdom: B1  frontier: [B1]
+    L2:
+    i#1 = i#1 + 1
+    goto L0
+B6  preds: [B1 B3]  succs: [B7]  idom: B1  ipdom: B7  frontier: []
+    L3:
+    callout(\"printf\", \"%d\", x#0)
+    return
+B7 (exit)  preds: [B6]  succs: []  idom: B6  ipdom: -  frontier: []
+loop B1  latches: [B5]  blocks: [B1 B2 B4 B5]
+";
+        assert_eq!(cfg.dump(&p), expected);
+    }
+
+    #[test]
+    fn test_dominators() {
+        let p = lower(SRC);
+        let cfg = Cfg::new(&p.functions[0]);
+        assert_eq!(cfg.reverse_postorder().first(), Some(&cfg.entry()));
+        assert!(cfg.dominates(1, 5));
+        assert!(!cfg.dominates(3, 6));
+        assert!(cfg.post_dominates(6, 0));
+        assert!(!cfg.post_dominates(4, 2));
+        assert_eq!(cfg.loops().len(), 1);
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let p = lower("class Program { int f() { return 1; return 2; } void main() { f(); } }");
+        let cfg = Cfg::new(&p.functions[0]);
+        assert_eq!(cfg.blocks().len(), 3);
+        assert_eq!(cfg.reverse_postorder(), &[0, 2]);
+        assert!(!cfg.is_reachable(1));
+        assert_eq!(cfg.preds(cfg.exit()), &[0, 1]);
+        assert_eq!(cfg.idom(cfg.exit()), Some(0));
+        assert_eq!(cfg.ipdom(1), Some(cfg.exit()));
+    }
+}
@@ -0,0 +1,460 @@
+//! lowering of the checked IR to three-address code
+
+use super::*;
+use crate::semantic_analyzer::ir;
+use std::collections::HashMap;
+
+struct Lowering<'a> {
+    ir: &'a ir::IRRoot,
+    program: Program,
+    vars: HashMap<ir::DeclId, Var>,
+    strings: HashMap<String, usize>,
+    /// code of the method being lowered
+    code: Vec<Instr>,
+    locals: Vec<Var>,
+    temps: usize,
+    labels: usize,
+    /// `continue` and `break` targets of the enclosing loops
+    loops: HashMap<ir::StmtId, (Label, Label)>,
+}
+
+impl<'a> Lowering<'a> {
+    fn declare(&mut self, d: ir::DeclId, kind: VarKind) -> Var {
+        let decl = self.ir.decl(d);
+        let v = Var(self.program.vars.len());
+        self.program.vars.push(Variable {
+            name: decl.name.clone(),
+            kind,
+            len: decl.arr_size.map(i64::from),
+        });
+        self.vars.insert(d, v);
+        if kind == VarKind::Local {
+            self.locals.push(v);
+        }
+        v
+    }
+
+    fn string(&mut self, s: &str) -> usize {
+        if let Some(&i) = self.strings.get(s) {
+            return i;
+        }
+        self.program.strings.push(s.to_string());
+        self.strings.insert(s.to_string(), self.program.strings.len() - 1);
+        self.program.strings.len() - 1
+    }
+
+    fn temp(&mut self) -> Temp {
+        self.temps += 1;
+        Temp(self.temps - 1)
+    }
+
+    fn label(&mut self) -> Label {
+        self.labels += 1;
+        Label(self.labels - 1)
+    }
+
+    fn emit(&mut self, i: Instr) {
+        self.code.push(i);
+    }
+
+    /// `src` in a new temporary
+    fn copy(&mut self, src: Operand) -> Operand {
+        let t = self.temp();
+        self.emit(Instr::Copy { dst: Place::Temp(t), src });
+        Operand::Temp(t)
+    }
+
+    fn is_global(&self, v: Var) -> bool {
+        self.program.var(v).kind == VarKind::Global
+    }
+
+    /// Checked index of an array location
+    fn index(&mut self, l: &ir::Location) -> Option<Operand> {
+        let index = self.expr(l.arr_size?);
+        let len = self.program.var(self.vars[&l.decl]).len.unwrap();
+        self.emit(Instr::BoundsCheck { index, len });
+        Some(index)
+    }
+
+    /// Value of `l`; a field is copied, since a call later in the same
+    /// expression could change it
+    fn read(&mut self, l: &ir::Location) -> Operand {
+        let var = self.vars[&l.decl];
+        match self.index(l) {
+            Some(index) => {
+                let t = self.temp();
+                self.emit(Instr::Load { dst: Place::Temp(t), array: var, index });
+                Operand::Temp(t)
+            }
+            None if self.is_global(var) => self.copy(Operand::Var(var)),
+            None => Operand::Var(var),
+        }
+    }
+
+    fn call(&mut self, c: &ir::Call, dst: Option<Place>) {
+        match c {
+            ir::Call::Method(m) => {
+                let args = m.args.iter().map(|&a| self.expr(a)).collect();
+                let method = self.ir.method(m.decl).name.clone();
+                self.emit(Instr::Call { dst, method, args });
+            }
+            ir::Call::Callout(c) => {
+                let args = c
+                    .args
+                    .iter()
+                    .map(|a| match a {
+                        ir::CalloutArg::Expr(e) => CalloutArg::Operand(self.expr(*e)),
+                        ir::CalloutArg::StringLiteral(s, _) => CalloutArg::Str(self.string(s)),
+                    })
+                    .collect();
+                self.emit(Instr::Callout { dst, name: c.name.clone(), args });
+            }
+        }
+    }
+
+    fn expr(&mut self, e: ir::ExprId) -> Operand {
+        let ir = self.ir;
+        match &ir.expr(e).expr {
+            ir::ExprType::Location(l) => self.read(l),
+            ir::ExprType::Literal(ir::Literal::Int(n)) => Operand::Const(i64::from(*n)),
+            ir::ExprType::Literal(ir::Literal::Boolean(b)) => Operand::Const(i64::from(*b)),
+            ir::ExprType::Call(c) => {
+                let t = self.temp();
+                self.call(c, Some(Place::Temp(t)));
+                Operand::Temp(t)
+            }
+            ir::ExprType::Unary(u) => {
+                let src = self.expr(u.expr);
+                let op = match u.op {
+                    ir::UnaryOp::NegInt => UnaryOp::Neg,
+                    ir::UnaryOp::NegBool => UnaryOp::Not,
+                };
+                let t = self.temp();
+                self.emit(Instr::Unary { dst: Place::Temp(t), op, src });
+                Operand::Temp(t)
+            }
+            ir::ExprType::Binary(b) => match binary_op(b.op) {
+                Some(op) => {
+                    let lhs = self.expr(b.lhs);
+                    let rhs = self.expr(b.rhs);
+                    let t = self.temp();
+                    self.emit(Instr::Binary { dst: Place::Temp(t), op, lhs, rhs });
+                    Operand::Temp(t)
+                }
+                None => self.bool_value(e),
+            },
+            ir::ExprType::Error => unreachable!("lowering an IR with errors"),
+        }
+    }
+
+    /// `0` or `1` for a `&&` or `||` expression, evaluated with jumps
+    fn bool_value(&mut self, e: ir::ExprId) -> Operand {
+        let (if_true, if_false, end) = (self.label(), self.label(), self.label());
+        let t = self.temp();
+        self.cond(e, if_true, if_false);
+        self.emit(Instr::Label(if_true));
+        self.emit(Instr::Copy { dst: Place::Temp(t), src: Operand::Const(1) });
+        self.emit(Instr::Jump(end));
+        self.emit(Instr::Label(if_false));
+        self.emit(Instr::Copy { dst: Place::Temp(t), src: Operand::Const(0) });
+        self.emit(Instr::Label(end));
+        Operand::Temp(t)
+    }
+
+    /// Jump to `if_true` or `if_false` depending on the boolean `e`,
+    /// evaluating the right operand of `&&` and `||` only if needed
+    fn cond(&mut self, e: ir::ExprId, if_true: Label, if_false: Label) {
+        let ir = self.ir;
+        match &ir.expr(e).expr {
+            ir::ExprType::Binary(b) if b.op == ir::BinaryOp::And => {
+                let rhs = self.label();
+                self.cond(b.lhs, rhs, if_false);
+                self.emit(Instr::Label(rhs));
+                self.cond(b.rhs, if_true, if_false);
+            }
+            ir::ExprType::Binary(b) if b.op == ir::BinaryOp::Or => {
+                let rhs = self.label();
+                self.cond(b.lhs, if_true, rhs);
+                self.emit(Instr::Label(rhs));
+                self.cond(b.rhs, if_true, if_false);
+            }
+            ir::ExprType::Unary(u) if u.op == ir::UnaryOp::NegBool => self.cond(u.expr, if_false, if_true),
+            ir::ExprType::Literal(ir::Literal::Boolean(b)) => {
+                self.emit(Instr::Jump(if *b { if_true } else { if_false }));
+            }
+            _ => {
+                let cond = self.expr(e);
+                self.emit(Instr::Branch { cond, if_true, if_false });
+            }
+        }
+    }
+
+    /// `dst op= val` reads `dst` before evaluating `val`, as `dst = dst op val`
+    fn assign(&mut self, a: &ir::Assign) {
+        let var = self.vars[&a.dst.decl];
+        let index = self.index(&a.dst);
+        let op = match a.op {
+            ir::AssignOp::Assign => None,
+            ir::AssignOp::AddAssign => Some(BinaryOp::Add),
+            ir::AssignOp::SubAssign => Some(BinaryOp::Sub),
+            ir::AssignOp::MulAssign => Some(BinaryOp::Mul),
+            ir::AssignOp::DivAssign => Some(BinaryOp::Div),
+        };
+        let lhs = op.map(|_| match index {
+            Some(index) => {
+                let t = self.temp();
+                self.emit(Instr::Load { dst: Place::Temp(t), array: var, index });
+                Operand::Temp(t)
+            }
+            None if self.is_global(var) => self.copy(Operand::Var(var)),
+            None => Operand::Var(var),
+        });
+        let mut src = self.expr(a.val);
+        if let (Some(op), Some(lhs)) = (op, lhs) {
+            let t = self.temp();
+            self.emit(Instr::Binary { dst: Place::Temp(t), op, lhs, rhs: src });
+            src = Operand::Temp(t);
+        }
+        match index {
+            Some(index) => self.emit(Instr::Store { array: var, index, src }),
+            None => self.emit(Instr::Copy { dst: Place::Var(var), src }),
+        }
+    }
+
+    /// `for i = start, end`: the bounds are evaluated once, before the
+    /// first iteration
+    fn for_(&mut self, id: ir::StmtId, l: &ir::For) {
+        let start = self.expr(l.start);
+        let end = match self.expr(l.end) {
+            Operand::Const(n) => Operand::Const(n),
+            end => self.copy(end),
+        };
+        let index = self.declare(l.index_decl, VarKind::Local);
+        self.emit(Instr::Copy { dst: Place::Var(index), src: start });
+        let (head, body, next, exit) = (self.label(), self.label(), self.label(), self.label());
+        self.emit(Instr::Label(head));
+        let cond = self.temp();
+        self.emit(Instr::Binary {
+            dst: Place::Temp(cond),
+            op: BinaryOp::Lt,
+            lhs: Operand::Var(index),
+            rhs: end,
+        });
+        self.emit(Instr::Branch { cond: Operand::Temp(cond), if_true: body, if_false: exit });
+        self.emit(Instr::Label(body));
+        self.loops.insert(id, (next, exit));
+        self.block(&l.block);
+        self.emit(Instr::Label(next));
+        self.emit(Instr::Binary {
+            dst: Place::Var(index),
+            op: BinaryOp::Add,
+            lhs: Operand::Var(index),
+            rhs: Operand::Const(1),
+        });
+        self.emit(Instr::Jump(head));
+        self.emit(Instr::Label(exit));
+    }
+
+    fn statement(&mut self, id: ir::StmtId) {
+        let ir = self.ir;
+        match ir.stmt(id) {
+            ir::Statement::Assign(a) => self.assign(a),
+            ir::Statement::Call(c) => self.call(c, None),
+            ir::Statement::IfElse(i) => {
+                let (then, end) = (self.label(), self.label());
+                let else_ = match i.false_block {
+                    Some(_) => self.label(),
+                    None => end,
+                };
+                self.cond(i.cond, then, else_);
+                self.emit(Instr::Label(then));
+                self.block(&i.true_block);
+                if let Some(b) = &i.false_block {
+                    self.emit(Instr::Jump(end));
+                    self.emit(Instr::Label(else_));
+                    self.block(b);
+                }
+                self.emit(Instr::Label(end));
+            }
+            ir::Statement::For(l) => self.for_(id, l),
+            ir::Statement::Return(r) => {
+                let val = r.val.map(|v| self.expr(v));
+                self.emit(Instr::Return(val));
+            }
+            ir::Statement::Break(b) => self.emit(Instr::Jump(self.loops[&b.for_].1)),
+            ir::Statement::Continue(c) => self.emit(Instr::Jump(self.loops[&c.for_].0)),
+            ir::Statement::Block(b) => self.block(b),
+        }
+    }
+
+    /// Local variables start at zero each time their block is entered
+    fn block(&mut self, b: &ir::Block) {
+        for &d in &b.var_decls {
+            let v = self.declare(d, VarKind::Local);
+            self.emit(Instr::Copy { dst: Place::Var(v), src: Operand::Const(0) });
+        }
+        for &s in &b.statements {
+            self.statement(s);
+        }
+    }
+
+    fn method(&mut self, m: &ir::MethodDecl) -> Function {
+        let params = m
+            .args
+            .iter()
+            .enumerate()
+            .map(|(i, &a)| self.declare(a, VarKind::Param(i)))
+            .collect();
+        self.block(&m.block);
+        let returns_value = m.return_type != ir::Type::Void;
+        if !returns_value {
+            self.emit(Instr::Return(None));
+        } else if m.needs_fall_off_trap {
+            self.emit(Instr::FallOff);
+        }
+        self.loops.clear();
+        Function {
+            name: m.name.clone(),
+            params,
+            locals: std::mem::take(&mut self.locals),
+            temps: std::mem::take(&mut self.temps),
+            labels: std::mem::take(&mut self.labels),
+            returns_value,
+            code: std::mem::take(&mut self.code),
+        }
+    }
+}
+
+/// Arithmetic and comparison operators; `None` for `&&` and `||`
+fn binary_op(op: ir::BinaryOp) -> Option<BinaryOp> {
+    Some(match op {
+        ir::BinaryOp::Add => BinaryOp::Add,
+        ir::BinaryOp::Sub => BinaryOp::Sub,
+        ir::BinaryOp::Mul => BinaryOp::Mul,
+        ir::BinaryOp::Div => BinaryOp::Div,
+        ir::BinaryOp::Mod => BinaryOp::Mod,
+        ir::BinaryOp::EQ => BinaryOp::Eq,
+        ir::BinaryOp::NE => BinaryOp::Ne,
+        ir::BinaryOp::LT => BinaryOp::Lt,
+        ir::BinaryOp::LE => BinaryOp::Le,
+        ir::BinaryOp::GT => BinaryOp::Gt,
+        ir::BinaryOp::GE => BinaryOp::Ge,
+        ir::BinaryOp::And | ir::BinaryOp::Or => return None,
+    })
+}
+
+/// Three-address code of a checked program
+pub fn lower(root: &ir::IRRoot) -> Program {
+    let mut l = Lowering {
+        ir: root,
+        program: Program::default(),
+        vars: HashMap::new(),
+        strings: HashMap::new(),
+        code: Vec::new(),
+        locals: Vec::new(),
+        temps: 0,
+        labels: 0,
+        loops: HashMap::new(),
+    };
+    for &d in &root.root.field_decls {
+        let g = l.declare(d, VarKind::Global);
+        l.program.globals.push(g);
+    }
+    for (_, m) in root.method_decls() {
+        let f = l.method(m);
+        l.program.functions.push(f);
+    }
+    l.program
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::lower;
+
+    #[test]
+    fn test_lower() {
+        let src = "class Program { int a[10], g; \
+                   int f(int x) { a[x] += g; return a[x]; } \
+                   void main() { boolean b; b = g > 0 && !(g == 1); \
+                   for i = 0, 10 { if (b || i < 2) { continue; } else { break; } } \
+                   callout(\"printf\", \"%d\\n\", f(1)); } }";
+        let expected = "\
+global a#0[10]
+global g#1
+
+f(x#2):
+    check 0 <= x#2 < 10
+    t0 = a#0[x#2]
+    t1 = g#1
+    t2 = t0 + t1
+    a#0[x#2] = t2
+    check 0 <= x#2 < 10
+    t3 = a#0[x#2]
+    return t3
+
+main():
+    b#3 = 0
+    t1 = g#1
+    t2 = t1 > 0
+    if t2 goto L3 else L1
+L3:
+    t3 = g#1
+    t4 = t3 == 1
+    if t4 goto L1 else L0
+L0:
+    t0 = 1
+    goto L2
+L1:
+    t0 = 0
+L2:
+    b#3 = t0
+    i#4 = 0
+L4:
+    t5 = i#4 < 10
+    if t5 goto L5 else L7
+L5:
+    if b#3 goto L8 else L11
+L11:
+    t6 = i#4 < 2
+    if t6 goto L8 else L10
+L8:
+    goto L6
+    goto L9
+L10:
+    goto L7
+L9:
+L6:
+    i#4 = i#4 + 1
+    goto L4
+L7:
+    t7 = call f(1)
+    callout(\"printf\", \"%d\\n\", t7)
+    return
+";
+        assert_eq!(lower(src).to_string(), expected);
+    }
+
+    #[test]
+    fn test_lower_strings_and_locals() {
+        let src = "class Program { void main() { int x; callout(\"printf\", \"a\"); \
+                   { int y; x = y; } callout(\"printf\", \"a\"); callout(\"puts\", \"b\"); } }";
+        let p = lower(src);
+        assert_eq!(p.strings, vec!["a", "b"]);
+        let main = &p.functions[0];
+        let locals: Vec<&str> = main.locals.iter().map(|&v| p.var(v).name.as_str()).collect();
+        assert_eq!(locals, vec!["x", "y"]);
+        assert!(!main.returns_value);
+        assert_eq!(main.code.last(), Some(&Instr::Return(None)));
+    }
+
+    #[test]
+    fn test_lower_fall_off() {
+        // only a method that can reach its end gets the trap
+        let src = "class Program { int f(boolean b) { if (b) { return 1; } } \
+                   int g(boolean b) { if (b) { return 1; } else { return 2; } } void main() { } }";
+        let p = lower(src);
+        assert_eq!(p.functions[0].code.last(), Some(&Instr::FallOff));
+        assert!(!p.functions[1].code.contains(&Instr::FallOff));
+    }
+}
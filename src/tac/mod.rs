@@ -0,0 +1,283 @@
+//! three-address code lowered from the IR
+//!
+//! Every method becomes a flat list of instructions over variables,
+//! numbered temporaries and labels. Booleans are `0` and `1`, `&&` and
+//! `||` are lowered to jumps, and every array access is preceded by an
+//! explicit bounds check.
+
+pub mod cfg;
+mod lower;
+
+pub use lower::lower;
+
+use std::fmt;
+
+/// Temporary of a function, assigned by the lowering
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Temp(pub usize);
+
+/// Jump target in a function
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Label(pub usize);
+
+/// Variable of the program, an index into [`Program::vars`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Var(pub usize);
+
+impl fmt::Display for Temp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "t{}", self.0)
+    }
+}
+
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "L{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VarKind {
+    Global,
+    /// parameter at the given position
+    Param(usize),
+    Local,
+}
+
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub name: String,
+    pub kind: VarKind,
+    /// number of elements of an array
+    pub len: Option<i64>,
+}
+
+/// Scalar that can be assigned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Place {
+    Temp(Temp),
+    Var(Var),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Const(i64),
+    Temp(Temp),
+    Var(Var),
+}
+
+impl From<Place> for Operand {
+    fn from(p: Place) -> Self {
+        match p {
+            Place::Temp(t) => Self::Temp(t),
+            Place::Var(v) => Self::Var(v),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl BinaryOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Mod => "%",
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalloutArg {
+    Operand(Operand),
+    /// string literal, an index into [`Program::strings`]
+    Str(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instr {
+    Copy { dst: Place, src: Operand },
+    Unary { dst: Place, op: UnaryOp, src: Operand },
+    Binary { dst: Place, op: BinaryOp, lhs: Operand, rhs: Operand },
+    /// stop with a runtime error unless `0 <= index < len`
+    BoundsCheck { index: Operand, len: i64 },
+    Load { dst: Place, array: Var, index: Operand },
+    Store { array: Var, index: Operand, src: Operand },
+    Call { dst: Option<Place>, method: String, args: Vec<Operand> },
+    Callout { dst: Option<Place>, name: String, args: Vec<CalloutArg> },
+    Label(Label),
+    Jump(Label),
+    /// jump to `if_true` if `cond` is not zero, else to `if_false`
+    Branch { cond: Operand, if_true: Label, if_false: Label },
+    Return(Option<Operand>),
+    /// stop with a runtime error: the end of a method returning a result
+    FallOff,
+}
+
+impl Instr {
+    /// Control never passes to the next instruction
+    pub fn is_terminator(&self) -> bool {
+        matches!(self, Self::Jump(_) | Self::Branch { .. } | Self::Return(_) | Self::FallOff)
+    }
+
+    /// Labels this instruction can jump to
+    pub fn targets(&self) -> Vec<Label> {
+        match self {
+            Self::Jump(l) => vec![*l],
+            Self::Branch { if_true, if_false, .. } => vec![*if_true, *if_false],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<Var>,
+    /// local variables and loop indexes, in source order
+    pub locals: Vec<Var>,
+    /// number of temporaries, `t0` to `t{temps - 1}`
+    pub temps: usize,
+    pub labels: usize,
+    pub returns_value: bool,
+    pub code: Vec<Instr>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub vars: Vec<Variable>,
+    pub globals: Vec<Var>,
+    /// string literals of the callouts, without duplicates
+    pub strings: Vec<String>,
+    pub functions: Vec<Function>,
+}
+
+impl Program {
+    pub fn var(&self, v: Var) -> &Variable {
+        &self.vars[v.0]
+    }
+
+    /// `name#id`, unique in the program
+    fn var_name(&self, v: Var) -> String {
+        format!("{}#{}", self.var(v).name, v.0)
+    }
+
+    fn operand(&self, o: Operand) -> String {
+        match o {
+            Operand::Const(n) => n.to_string(),
+            Operand::Temp(t) => t.to_string(),
+            Operand::Var(v) => self.var_name(v),
+        }
+    }
+
+    fn place(&self, p: Place) -> String {
+        self.operand(p.into())
+    }
+
+    /// `dst = ` of a call with a result
+    fn assign_to(&self, dst: Option<Place>) -> String {
+        match dst {
+            Some(p) => format!("{} = ", self.place(p)),
+            None => String::new(),
+        }
+    }
+
+    /// Text of one instruction, as in the dump of the program
+    pub fn instr(&self, i: &Instr) -> String {
+        match i {
+            Instr::Copy { dst, src } => format!("{} = {}", self.place(*dst), self.operand(*src)),
+            Instr::Unary { dst, op, src } => {
+                let op = match op {
+                    UnaryOp::Neg => "-",
+                    UnaryOp::Not => "!",
+                };
+                format!("{} = {}{}", self.place(*dst), op, self.operand(*src))
+            }
+            Instr::Binary { dst, op, lhs, rhs } => format!(
+                "{} = {} {} {}",
+                self.place(*dst),
+                self.operand(*lhs),
+                op.symbol(),
+                self.operand(*rhs)
+            ),
+            Instr::BoundsCheck { index, len } => format!("check 0 <= {} < {}", self.operand(*index), len),
+            Instr::Load { dst, array, index } => {
+                format!("{} = {}[{}]", self.place(*dst), self.var_name(*array), self.operand(*index))
+            }
+            Instr::Store { array, index, src } => {
+                format!("{}[{}] = {}", self.var_name(*array), self.operand(*index), self.operand(*src))
+            }
+            Instr::Call { dst, method, args } => {
+                let args: Vec<String> = args.iter().map(|&a| self.operand(a)).collect();
+                format!("{}call {}({})", self.assign_to(*dst), method, args.join(", "))
+            }
+            Instr::Callout { dst, name, args } => {
+                let mut text = vec![format!("{:?}", name)];
+                text.extend(args.iter().map(|a| match a {
+                    CalloutArg::Operand(o) => self.operand(*o),
+                    CalloutArg::Str(s) => format!("{:?}", self.strings[*s]),
+                }));
+                format!("{}callout({})", self.assign_to(*dst), text.join(", "))
+            }
+            Instr::Label(l) => format!("{}:", l),
+            Instr::Jump(l) => format!("goto {}", l),
+            Instr::Branch { cond, if_true, if_false } => {
+                format!("if {} goto {} else {}", self.operand(*cond), if_true, if_false)
+            }
+            Instr::Return(Some(v)) => format!("return {}", self.operand(*v)),
+            Instr::Return(None) => String::from("return"),
+            Instr::FallOff => String::from("fall off"),
+        }
+    }
+}
+
+/// Globals, then every function with labels flush left and the other
+/// instructions indented
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &g in &self.globals {
+            match self.var(g).len {
+                Some(n) => writeln!(f, "global {}[{}]", self.var_name(g), n)?,
+                None => writeln!(f, "global {}", self.var_name(g))?,
+            }
+        }
+        for func in &self.functions {
+            let params: Vec<String> = func.params.iter().map(|&p| self.var_name(p)).collect();
+            writeln!(f, "\n{}({}):", func.name, params.join(", "))?;
+            for i in &func.code {
+                match i {
+                    Instr::Label(_) => writeln!(f, "{}", self.instr(i))?,
+                    _ => writeln!(f, "    {}", self.instr(i))?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,3 +1,7 @@
+use crate::parser::parse;
+use crate::semantic_analyzer::ir::IRRoot;
+use crate::semantic_analyzer::SemanticAnalyzer;
+use crate::tac::{self, Program};
 use std::env;
 
 pub fn get_current_dir() -> String {
@@ -8,3 +12,13 @@ pub fn get_current_dir() -> String {
         String::from(env::current_dir().unwrap().to_str().unwrap())
     }
 }
+
+/// IR of the legal program `src`
+pub fn check(src: &str) -> IRRoot {
+    SemanticAnalyzer::new().create_ir(parse(src).unwrap()).unwrap()
+}
+
+/// three-address code of the legal program `src`
+pub fn lower(src: &str) -> Program {
+    tac::lower(&check(src))
+}
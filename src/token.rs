@@ -1,19 +1,24 @@
+use crate::span::Span;
+
 #[derive(Debug)]
 pub struct FieldDecl {
     pub type_: Type,
     pub loc: Vec<FieldDecl0>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct VarDecl {
     pub type_: Type,
     pub identifiers: Vec<Identifier>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct Block {
     pub var_decls: Vec<VarDecl>,
     pub statements: Vec<Statement>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
@@ -21,12 +26,14 @@ pub struct Assign {
     pub dst: Location,
     pub op: AssignOp,
     pub val: Expr,
+    pub span: Span,
 }
 #[derive(Debug)]
 pub struct IfElse {
     pub cond: Expr,
     pub true_block: Block,
     pub false_block: Option<Block>,
+    pub span: Span,
 }
 #[derive(Debug)]
 pub struct Loop {
@@ -34,11 +41,13 @@ pub struct Loop {
     pub start: Expr,
     pub end: Expr,
     pub block: Block,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct Return {
     pub val: Option<Expr>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
@@ -48,27 +57,45 @@ pub enum Statement {
     IfElse(IfElse),
     Loop(Loop),
     Return(Return),
-    Break,
-    Continue,
+    Break(Span),
+    Continue(Span),
     Block(Block),
 }
 
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Assign(s) => s.span,
+            Self::MethodCall(s) => s.span(),
+            Self::IfElse(s) => s.span,
+            Self::Loop(s) => s.span,
+            Self::Return(s) => s.span,
+            Self::Break(s) => *s,
+            Self::Continue(s) => *s,
+            Self::Block(s) => s.span,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Location {
-    pub name: String,
+    pub name: Identifier,
     pub arr_size: Option<Expr>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct FieldDecl0 {
-    pub name: String,
-    pub arr_size: Option<i32>,
+    pub name: Identifier,
+    pub arr_size: Option<IntLiteral>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct MethodArg {
     pub type_: Type,
     pub name: Identifier,
+    pub span: Span,
 }
 
 #[derive(Debug)]
@@ -77,19 +104,34 @@ pub struct MethodDecl {
     pub name: Identifier,
     pub args: Vec<MethodArg>,
     pub block: Block,
+    /// the signature had a syntax error, so parameters may be missing
+    pub recovered: bool,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct Program {
     pub field_decls: Vec<FieldDecl>,
     pub method_decls: Vec<MethodDecl>,
+    pub span: Span,
 }
 
-pub type MethodName = String;
-pub type Identifier = String;
+pub type MethodName = Identifier;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identifier {
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Literal {
+    pub kind: LiteralKind,
+    pub span: Span,
+}
 
 #[derive(Debug)]
-pub enum Literal {
+pub enum LiteralKind {
     Int(IntLiteral),
     Bool(BoolLiteral),
     Char(CharLiteral),
@@ -148,9 +190,9 @@ pub enum CondOp {
     And,
 }
 
-pub type IntLiteral = i32;
-pub type DecimalLiteral = i32;
-pub type HexLiteral = i32;
+/// decimal or hex literal as written in the source, range checked
+/// by the semantic analyzer
+pub type IntLiteral = String;
 
 #[derive(Debug, Clone)]
 pub enum BoolLiteral {
@@ -165,6 +207,7 @@ pub struct Binary {
     pub lhs: Expr,
     pub rhs: Expr,
     pub op: BinaryOp,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -177,6 +220,7 @@ pub enum UnaryOp {
 pub struct Unary {
     pub expr: Expr,
     pub op: UnaryOp,
+    pub span: Span,
 }
 
 pub type Expr = Box<Expr0>;
@@ -190,22 +234,36 @@ pub enum Expr0 {
     Binary(Binary),
 }
 
+impl Expr0 {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Location(e) => e.span,
+            Self::MethodCall(e) => e.span(),
+            Self::Literal(e) => e.span,
+            Self::Unary(e) => e.span,
+            Self::Binary(e) => e.span,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum CalloutArg {
     Expr(Expr),
-    StringLiteral(StringLiteral),
+    StringLiteral(StringLiteral, Span),
 }
 
 #[derive(Debug)]
 pub struct Method {
     pub name: MethodName,
     pub args: Vec<Expr>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct Callout {
     pub name: StringLiteral,
     pub args: Vec<CalloutArg>,
+    pub span: Span,
 }
 
 
@@ -215,3 +273,12 @@ pub enum MethodCall {
     Callout (Callout),
 
 }
+
+impl MethodCall {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Method(m) => m.span,
+            Self::Callout(c) => c.span,
+        }
+    }
+}
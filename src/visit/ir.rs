@@ -0,0 +1,363 @@
+//! visitors over the IR
+//!
+//! Nodes in the arenas of [`IRRoot`] are visited by id, with the root
+//! passed along. The mutable visitor gets `&mut IRRoot` and rewrites a
+//! node through its id; blocks, locations and calls are part of the
+//! statement, expression or method holding them, and are visited as a
+//! copy written back to it once its children are walked.
+
+use crate::semantic_analyzer::ir::*;
+use crate::semantic_analyzer::symbols::SymbolKind;
+
+/// Read-only traversal of an [`IRRoot`]
+pub trait Visitor<'a> {
+    fn visit_program(&mut self, ir: &'a IRRoot) {
+        walk_program(self, ir)
+    }
+
+    /// Declaration of a field, parameter, local or loop index
+    fn visit_var_decl(&mut self, _ir: &'a IRRoot, _id: DeclId, _kind: SymbolKind) {}
+
+    fn visit_method_decl(&mut self, ir: &'a IRRoot, id: MethodId) {
+        walk_method_decl(self, ir, id)
+    }
+
+    fn visit_block(&mut self, ir: &'a IRRoot, b: &'a Block) {
+        walk_block(self, ir, b)
+    }
+
+    fn visit_statement(&mut self, ir: &'a IRRoot, id: StmtId) {
+        walk_statement(self, ir, id)
+    }
+
+    /// Called for assignment destinations as well as reads
+    fn visit_location(&mut self, ir: &'a IRRoot, l: &'a Location) {
+        walk_location(self, ir, l)
+    }
+
+    fn visit_call(&mut self, ir: &'a IRRoot, c: &'a Call) {
+        walk_call(self, ir, c)
+    }
+
+    fn visit_expr(&mut self, ir: &'a IRRoot, id: ExprId) {
+        walk_expr(self, ir, id)
+    }
+}
+
+pub fn walk_program<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ir: &'a IRRoot) {
+    for &d in &ir.root.field_decls {
+        v.visit_var_decl(ir, d, SymbolKind::Field);
+    }
+    for &m in &ir.root.method_decls {
+        v.visit_method_decl(ir, m);
+    }
+}
+
+pub fn walk_method_decl<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ir: &'a IRRoot, id: MethodId) {
+    let m = ir.method(id);
+    for &a in &m.args {
+        v.visit_var_decl(ir, a, SymbolKind::Parameter);
+    }
+    v.visit_block(ir, &m.block);
+}
+
+pub fn walk_block<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ir: &'a IRRoot, b: &'a Block) {
+    for &d in &b.var_decls {
+        v.visit_var_decl(ir, d, SymbolKind::Local);
+    }
+    for &s in &b.statements {
+        v.visit_statement(ir, s);
+    }
+}
+
+pub fn walk_statement<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ir: &'a IRRoot, id: StmtId) {
+    match ir.stmt(id) {
+        Statement::Assign(a) => {
+            v.visit_location(ir, &a.dst);
+            v.visit_expr(ir, a.val);
+        }
+        Statement::Call(c) => v.visit_call(ir, c),
+        Statement::IfElse(i) => {
+            v.visit_expr(ir, i.cond);
+            v.visit_block(ir, &i.true_block);
+            if let Some(b) = &i.false_block {
+                v.visit_block(ir, b);
+            }
+        }
+        Statement::For(l) => {
+            // the bounds are evaluated before the index is declared
+            v.visit_expr(ir, l.start);
+            v.visit_expr(ir, l.end);
+            v.visit_var_decl(ir, l.index_decl, SymbolKind::LoopIndex);
+            v.visit_block(ir, &l.block);
+        }
+        Statement::Return(r) => {
+            if let Some(e) = r.val {
+                v.visit_expr(ir, e);
+            }
+        }
+        Statement::Break(_) | Statement::Continue(_) => (),
+        Statement::Block(b) => v.visit_block(ir, b),
+    }
+}
+
+pub fn walk_location<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ir: &'a IRRoot, l: &'a Location) {
+    if let Some(i) = l.arr_size {
+        v.visit_expr(ir, i);
+    }
+}
+
+pub fn walk_call<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ir: &'a IRRoot, c: &'a Call) {
+    match c {
+        Call::Method(m) => m.args.iter().for_each(|&a| v.visit_expr(ir, a)),
+        Call::Callout(c) => {
+            for a in &c.args {
+                if let CalloutArg::Expr(e) = a {
+                    v.visit_expr(ir, *e);
+                }
+            }
+        }
+    }
+}
+
+pub fn walk_expr<'a, V: Visitor<'a> + ?Sized>(v: &mut V, ir: &'a IRRoot, id: ExprId) {
+    match &ir.expr(id).expr {
+        ExprType::Location(l) => v.visit_location(ir, l),
+        ExprType::Call(c) => v.visit_call(ir, c),
+        ExprType::Unary(u) => v.visit_expr(ir, u.expr),
+        ExprType::Binary(b) => {
+            v.visit_expr(ir, b.lhs);
+            v.visit_expr(ir, b.rhs);
+        }
+        ExprType::Literal(_) | ExprType::Error => (),
+    }
+}
+
+/// Traversal of an [`IRRoot`] that can change its nodes
+///
+/// `walk_*` visits the children a node has when it is called, so a
+/// visitor replacing a child does it before walking the node. A node
+/// holding a block, location or call is written back after the walk,
+/// which undoes changes made to it through its id by its children.
+pub trait VisitorMut {
+    fn visit_program_mut(&mut self, ir: &mut IRRoot) {
+        walk_program_mut(self, ir)
+    }
+
+    fn visit_var_decl_mut(&mut self, _ir: &mut IRRoot, _id: DeclId, _kind: SymbolKind) {}
+
+    fn visit_method_decl_mut(&mut self, ir: &mut IRRoot, id: MethodId) {
+        walk_method_decl_mut(self, ir, id)
+    }
+
+    fn visit_block_mut(&mut self, ir: &mut IRRoot, b: &mut Block) {
+        walk_block_mut(self, ir, b)
+    }
+
+    fn visit_statement_mut(&mut self, ir: &mut IRRoot, id: StmtId) {
+        walk_statement_mut(self, ir, id)
+    }
+
+    /// Called for assignment destinations as well as reads
+    fn visit_location_mut(&mut self, ir: &mut IRRoot, l: &mut Location) {
+        walk_location_mut(self, ir, l)
+    }
+
+    fn visit_call_mut(&mut self, ir: &mut IRRoot, c: &mut Call) {
+        walk_call_mut(self, ir, c)
+    }
+
+    fn visit_expr_mut(&mut self, ir: &mut IRRoot, id: ExprId) {
+        walk_expr_mut(self, ir, id)
+    }
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(v: &mut V, ir: &mut IRRoot) {
+    for d in ir.root.field_decls.clone() {
+        v.visit_var_decl_mut(ir, d, SymbolKind::Field);
+    }
+    for m in ir.root.method_decls.clone() {
+        v.visit_method_decl_mut(ir, m);
+    }
+}
+
+pub fn walk_method_decl_mut<V: VisitorMut + ?Sized>(v: &mut V, ir: &mut IRRoot, id: MethodId) {
+    for a in ir.method(id).args.clone() {
+        v.visit_var_decl_mut(ir, a, SymbolKind::Parameter);
+    }
+    let mut block = ir.method(id).block.clone();
+    v.visit_block_mut(ir, &mut block);
+    ir.method_mut(id).block = block;
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(v: &mut V, ir: &mut IRRoot, b: &mut Block) {
+    for &d in &b.var_decls {
+        v.visit_var_decl_mut(ir, d, SymbolKind::Local);
+    }
+    for &s in &b.statements {
+        v.visit_statement_mut(ir, s);
+    }
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, ir: &mut IRRoot, id: StmtId) {
+    let mut s = ir.stmt(id).clone();
+    match &mut s {
+        Statement::Assign(a) => {
+            v.visit_location_mut(ir, &mut a.dst);
+            v.visit_expr_mut(ir, a.val);
+        }
+        Statement::Call(c) => v.visit_call_mut(ir, c),
+        Statement::IfElse(i) => {
+            v.visit_expr_mut(ir, i.cond);
+            v.visit_block_mut(ir, &mut i.true_block);
+            if let Some(b) = &mut i.false_block {
+                v.visit_block_mut(ir, b);
+            }
+        }
+        Statement::For(l) => {
+            v.visit_expr_mut(ir, l.start);
+            v.visit_expr_mut(ir, l.end);
+            v.visit_var_decl_mut(ir, l.index_decl, SymbolKind::LoopIndex);
+            v.visit_block_mut(ir, &mut l.block);
+        }
+        Statement::Return(r) => {
+            if let Some(e) = r.val {
+                v.visit_expr_mut(ir, e);
+            }
+        }
+        Statement::Break(_) | Statement::Continue(_) => return,
+        Statement::Block(b) => v.visit_block_mut(ir, b),
+    }
+    *ir.stmt_mut(id) = s;
+}
+
+pub fn walk_location_mut<V: VisitorMut + ?Sized>(v: &mut V, ir: &mut IRRoot, l: &mut Location) {
+    if let Some(i) = l.arr_size {
+        v.visit_expr_mut(ir, i);
+    }
+}
+
+pub fn walk_call_mut<V: VisitorMut + ?Sized>(v: &mut V, ir: &mut IRRoot, c: &mut Call) {
+    match c {
+        Call::Method(m) => m.args.iter().for_each(|&a| v.visit_expr_mut(ir, a)),
+        Call::Callout(c) => {
+            for a in &c.args {
+                if let CalloutArg::Expr(e) = a {
+                    v.visit_expr_mut(ir, *e);
+                }
+            }
+        }
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(v: &mut V, ir: &mut IRRoot, id: ExprId) {
+    let mut e = ir.expr(id).expr.clone();
+    match &mut e {
+        ExprType::Location(l) => v.visit_location_mut(ir, l),
+        ExprType::Call(c) => v.visit_call_mut(ir, c),
+        ExprType::Unary(u) => return v.visit_expr_mut(ir, u.expr),
+        ExprType::Binary(b) => {
+            v.visit_expr_mut(ir, b.lhs);
+            return v.visit_expr_mut(ir, b.rhs);
+        }
+        ExprType::Literal(_) | ExprType::Error => return,
+    }
+    ir.expr_mut(id).expr = e;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantic_analyzer::printer;
+    use crate::test_util::check;
+
+    const SRC: &str = "class Program { int a[10]; int f(int n) { return n * 2; } \
+                       void main() { int x; x = f(a[1]) + 3; for i = 0, x { a[i] = i - 1; } } }";
+
+    /// declarations and locations, in source order
+    #[derive(Default)]
+    struct Names(Vec<String>);
+
+    impl<'a> Visitor<'a> for Names {
+        fn visit_var_decl(&mut self, ir: &'a IRRoot, id: DeclId, kind: SymbolKind) {
+            self.0.push(format!("{:?} {}", kind, ir.decl(id).name));
+        }
+
+        fn visit_location(&mut self, ir: &'a IRRoot, l: &'a Location) {
+            self.0.push(ir.decl(l.decl).name.clone());
+            walk_location(self, ir, l);
+        }
+    }
+
+    /// replaces every integer literal `n` with `n + 1`
+    struct Increment;
+
+    impl VisitorMut for Increment {
+        fn visit_expr_mut(&mut self, ir: &mut IRRoot, id: ExprId) {
+            if let ExprType::Literal(Literal::Int(n)) = &mut ir.expr_mut(id).expr {
+                *n += 1;
+            }
+            walk_expr_mut(self, ir, id);
+        }
+    }
+
+    /// reads and writes `x` instead of the loop index, drops the
+    /// arguments of calls and repeats the statements of nested blocks
+    struct Rewrite(Option<DeclId>);
+
+    impl VisitorMut for Rewrite {
+        fn visit_var_decl_mut(&mut self, ir: &mut IRRoot, id: DeclId, _kind: SymbolKind) {
+            if ir.decl(id).name == "x" {
+                self.0 = Some(id);
+            }
+        }
+
+        fn visit_block_mut(&mut self, ir: &mut IRRoot, b: &mut Block) {
+            walk_block_mut(self, ir, b);
+            if self.0.is_some() && b.var_decls.is_empty() {
+                b.statements.extend(b.statements.clone());
+            }
+        }
+
+        fn visit_location_mut(&mut self, ir: &mut IRRoot, l: &mut Location) {
+            if ir.decl(l.decl).name == "i" {
+                l.decl = self.0.unwrap();
+            }
+            walk_location_mut(self, ir, l);
+        }
+
+        fn visit_call_mut(&mut self, _ir: &mut IRRoot, c: &mut Call) {
+            if let Call::Method(m) = c {
+                m.args.clear();
+            }
+        }
+    }
+
+    #[test]
+    fn test_visitor() {
+        let ir = check(SRC);
+        let mut names = Names::default();
+        names.visit_program(&ir);
+        assert_eq!(
+            names.0,
+            vec!["Field a", "Parameter n", "n", "Local x", "x", "a", "x", "LoopIndex i", "a", "i", "i"]
+        );
+    }
+
+    #[test]
+    fn test_visitor_mut() {
+        let mut ir = check(SRC);
+        Increment.visit_program_mut(&mut ir);
+        let out = printer::print_ir(&ir);
+        assert!(out.contains("return[f#1] (n#2: int * 3: int): int"), "{}", out);
+        assert!(out.contains("x#4 = (f#1(a#0[2: int]: int): int + 4: int): int"), "{}", out);
+        assert!(out.contains("for i#5 = 1: int, x#4: int {"), "{}", out);
+
+        let mut ir = check(SRC);
+        Rewrite(None).visit_program_mut(&mut ir);
+        let out = printer::print_ir(&ir);
+        assert!(out.contains("x#4 = (f#1(): int + 3: int): int"), "{}", out);
+        let body = "a#0[x#4: int] = (x#4: int - 1: int): int\n";
+        assert!(out.contains(&format!("x#4: int {{\n        {}        {}    }}", body, body)), "{}", out);
+    }
+}
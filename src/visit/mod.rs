@@ -0,0 +1,10 @@
+//! visitors over the token tree and the IR
+//!
+//! Each tree has a `Visitor` over shared references and a `VisitorMut`
+//! that can change the nodes. Every `visit_*` method defaults to the
+//! `walk_*` function of the same name, which visits the children of the
+//! node in source order, so a visitor only overrides the nodes it cares
+//! about and calls `walk_*` to keep descending.
+
+pub mod ir;
+pub mod token;
@@ -0,0 +1,281 @@
+//! visitors over the token tree built by the parser
+
+use crate::token::*;
+
+/// Read-only traversal of a [`Program`]
+pub trait Visitor<'a> {
+    fn visit_program(&mut self, p: &'a Program) {
+        walk_program(self, p)
+    }
+
+    fn visit_field_decl(&mut self, _d: &'a FieldDecl) {}
+
+    fn visit_method_decl(&mut self, m: &'a MethodDecl) {
+        walk_method_decl(self, m)
+    }
+
+    fn visit_method_arg(&mut self, _a: &'a MethodArg) {}
+
+    fn visit_block(&mut self, b: &'a Block) {
+        walk_block(self, b)
+    }
+
+    fn visit_var_decl(&mut self, _d: &'a VarDecl) {}
+
+    fn visit_statement(&mut self, s: &'a Statement) {
+        walk_statement(self, s)
+    }
+
+    /// Called for assignment destinations as well as reads
+    fn visit_location(&mut self, l: &'a Location) {
+        walk_location(self, l)
+    }
+
+    fn visit_method_call(&mut self, c: &'a MethodCall) {
+        walk_method_call(self, c)
+    }
+
+    fn visit_expr(&mut self, e: &'a Expr0) {
+        walk_expr(self, e)
+    }
+
+    fn visit_literal(&mut self, _l: &'a Literal) {}
+}
+
+pub fn walk_program<'a, V: Visitor<'a> + ?Sized>(v: &mut V, p: &'a Program) {
+    p.field_decls.iter().for_each(|d| v.visit_field_decl(d));
+    p.method_decls.iter().for_each(|m| v.visit_method_decl(m));
+}
+
+pub fn walk_method_decl<'a, V: Visitor<'a> + ?Sized>(v: &mut V, m: &'a MethodDecl) {
+    m.args.iter().for_each(|a| v.visit_method_arg(a));
+    v.visit_block(&m.block);
+}
+
+pub fn walk_block<'a, V: Visitor<'a> + ?Sized>(v: &mut V, b: &'a Block) {
+    b.var_decls.iter().for_each(|d| v.visit_var_decl(d));
+    b.statements.iter().for_each(|s| v.visit_statement(s));
+}
+
+pub fn walk_statement<'a, V: Visitor<'a> + ?Sized>(v: &mut V, s: &'a Statement) {
+    match s {
+        Statement::Assign(a) => {
+            v.visit_location(&a.dst);
+            v.visit_expr(&a.val);
+        }
+        Statement::MethodCall(c) => v.visit_method_call(c),
+        Statement::IfElse(i) => {
+            v.visit_expr(&i.cond);
+            v.visit_block(&i.true_block);
+            if let Some(b) = &i.false_block {
+                v.visit_block(b);
+            }
+        }
+        Statement::Loop(l) => {
+            v.visit_expr(&l.start);
+            v.visit_expr(&l.end);
+            v.visit_block(&l.block);
+        }
+        Statement::Return(r) => {
+            if let Some(e) = &r.val {
+                v.visit_expr(e);
+            }
+        }
+        Statement::Break(_) | Statement::Continue(_) => (),
+        Statement::Block(b) => v.visit_block(b),
+    }
+}
+
+pub fn walk_location<'a, V: Visitor<'a> + ?Sized>(v: &mut V, l: &'a Location) {
+    if let Some(i) = &l.arr_size {
+        v.visit_expr(i);
+    }
+}
+
+pub fn walk_method_call<'a, V: Visitor<'a> + ?Sized>(v: &mut V, c: &'a MethodCall) {
+    match c {
+        MethodCall::Method(m) => m.args.iter().for_each(|a| v.visit_expr(a)),
+        MethodCall::Callout(c) => {
+            for a in &c.args {
+                if let CalloutArg::Expr(e) = a {
+                    v.visit_expr(e);
+                }
+            }
+        }
+    }
+}
+
+pub fn walk_expr<'a, V: Visitor<'a> + ?Sized>(v: &mut V, e: &'a Expr0) {
+    match e {
+        Expr0::Location(l) => v.visit_location(l),
+        Expr0::MethodCall(c) => v.visit_method_call(c),
+        Expr0::Literal(l) => v.visit_literal(l),
+        Expr0::Unary(u) => v.visit_expr(&u.expr),
+        Expr0::Binary(b) => {
+            v.visit_expr(&b.lhs);
+            v.visit_expr(&b.rhs);
+        }
+    }
+}
+
+/// Traversal of a [`Program`] that can change its nodes
+pub trait VisitorMut {
+    fn visit_program_mut(&mut self, p: &mut Program) {
+        walk_program_mut(self, p)
+    }
+
+    fn visit_field_decl_mut(&mut self, _d: &mut FieldDecl) {}
+
+    fn visit_method_decl_mut(&mut self, m: &mut MethodDecl) {
+        walk_method_decl_mut(self, m)
+    }
+
+    fn visit_method_arg_mut(&mut self, _a: &mut MethodArg) {}
+
+    fn visit_block_mut(&mut self, b: &mut Block) {
+        walk_block_mut(self, b)
+    }
+
+    fn visit_var_decl_mut(&mut self, _d: &mut VarDecl) {}
+
+    fn visit_statement_mut(&mut self, s: &mut Statement) {
+        walk_statement_mut(self, s)
+    }
+
+    /// Called for assignment destinations as well as reads
+    fn visit_location_mut(&mut self, l: &mut Location) {
+        walk_location_mut(self, l)
+    }
+
+    fn visit_method_call_mut(&mut self, c: &mut MethodCall) {
+        walk_method_call_mut(self, c)
+    }
+
+    fn visit_expr_mut(&mut self, e: &mut Expr0) {
+        walk_expr_mut(self, e)
+    }
+
+    fn visit_literal_mut(&mut self, _l: &mut Literal) {}
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(v: &mut V, p: &mut Program) {
+    p.field_decls.iter_mut().for_each(|d| v.visit_field_decl_mut(d));
+    p.method_decls.iter_mut().for_each(|m| v.visit_method_decl_mut(m));
+}
+
+pub fn walk_method_decl_mut<V: VisitorMut + ?Sized>(v: &mut V, m: &mut MethodDecl) {
+    m.args.iter_mut().for_each(|a| v.visit_method_arg_mut(a));
+    v.visit_block_mut(&mut m.block);
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(v: &mut V, b: &mut Block) {
+    b.var_decls.iter_mut().for_each(|d| v.visit_var_decl_mut(d));
+    b.statements.iter_mut().for_each(|s| v.visit_statement_mut(s));
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, s: &mut Statement) {
+    match s {
+        Statement::Assign(a) => {
+            v.visit_location_mut(&mut a.dst);
+            v.visit_expr_mut(&mut a.val);
+        }
+        Statement::MethodCall(c) => v.visit_method_call_mut(c),
+        Statement::IfElse(i) => {
+            v.visit_expr_mut(&mut i.cond);
+            v.visit_block_mut(&mut i.true_block);
+            if let Some(b) = &mut i.false_block {
+                v.visit_block_mut(b);
+            }
+        }
+        Statement::Loop(l) => {
+            v.visit_expr_mut(&mut l.start);
+            v.visit_expr_mut(&mut l.end);
+            v.visit_block_mut(&mut l.block);
+        }
+        Statement::Return(r) => {
+            if let Some(e) = &mut r.val {
+                v.visit_expr_mut(e);
+            }
+        }
+        Statement::Break(_) | Statement::Continue(_) => (),
+        Statement::Block(b) => v.visit_block_mut(b),
+    }
+}
+
+pub fn walk_location_mut<V: VisitorMut + ?Sized>(v: &mut V, l: &mut Location) {
+    if let Some(i) = &mut l.arr_size {
+        v.visit_expr_mut(i);
+    }
+}
+
+pub fn walk_method_call_mut<V: VisitorMut + ?Sized>(v: &mut V, c: &mut MethodCall) {
+    match c {
+        MethodCall::Method(m) => m.args.iter_mut().for_each(|a| v.visit_expr_mut(a)),
+        MethodCall::Callout(c) => {
+            for a in &mut c.args {
+                if let CalloutArg::Expr(e) = a {
+                    v.visit_expr_mut(e);
+                }
+            }
+        }
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(v: &mut V, e: &mut Expr0) {
+    match e {
+        Expr0::Location(l) => v.visit_location_mut(l),
+        Expr0::MethodCall(c) => v.visit_method_call_mut(c),
+        Expr0::Literal(l) => v.visit_literal_mut(l),
+        Expr0::Unary(u) => v.visit_expr_mut(&mut u.expr),
+        Expr0::Binary(b) => {
+            v.visit_expr_mut(&mut b.lhs);
+            v.visit_expr_mut(&mut b.rhs);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    /// names of the locations in source order
+    #[derive(Default)]
+    struct Names<'a>(Vec<&'a str>);
+
+    impl<'a> Visitor<'a> for Names<'a> {
+        fn visit_location(&mut self, l: &'a Location) {
+            self.0.push(&l.name.name);
+            walk_location(self, l);
+        }
+    }
+
+    /// renames every location `from` to `to`
+    struct Rename(&'static str, &'static str);
+
+    impl VisitorMut for Rename {
+        fn visit_location_mut(&mut self, l: &mut Location) {
+            if l.name.name == self.0 {
+                l.name.name = String::from(self.1);
+            }
+            walk_location_mut(self, l);
+        }
+    }
+
+    #[test]
+    fn test_visitor() {
+        let mut p = parse(
+            "class Program { int a[10]; void main() { int x; x = a[x] + f(x); \
+             for i = 0, x { if (i > 0) { a[i] += x; } } callout(\"printf\", \"%d\", x); } }",
+        )
+        .unwrap();
+        let mut names = Names::default();
+        names.visit_program(&p);
+        assert_eq!(names.0, vec!["x", "a", "x", "x", "x", "i", "a", "i", "x", "x"]);
+
+        Rename("x", "y").visit_program_mut(&mut p);
+        let mut names = Names::default();
+        names.visit_program(&p);
+        assert_eq!(names.0, vec!["y", "a", "y", "y", "y", "i", "a", "i", "y", "y"]);
+    }
+}